@@ -0,0 +1,198 @@
+/// A simple RGB24 drawing surface for diagnostic images (contact sheets, scopes).
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+pub type Rgb = [u8; 3];
+
+/// Glyph cell size of the built-in font, before scaling.
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+impl Canvas {
+    /// Wrap an existing RGB24 buffer.
+    pub fn from_rgb(width: u32, height: u32, data: Vec<u8>) -> Self {
+        Canvas {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        let data = background.repeat((width * height) as usize);
+        Canvas {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: i64, y: i64, color: Rgb) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let idx = (y as usize * self.width as usize + x as usize) * 3;
+        self.data[idx..idx + 3].copy_from_slice(&color);
+    }
+
+    pub fn fill_rect(&mut self, x: i64, y: i64, w: u32, h: u32, color: Rgb) {
+        for dy in 0..h as i64 {
+            for dx in 0..w as i64 {
+                self.set_pixel(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Draw a 1-pixel line (Bresenham).
+    pub fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    pub fn rect_outline(&mut self, x: i64, y: i64, w: u32, h: u32, color: Rgb) {
+        let (x1, y1) = (x + w as i64 - 1, y + h as i64 - 1);
+        self.line(x, y, x1, y, color);
+        self.line(x, y1, x1, y1, color);
+        self.line(x, y, x, y1, color);
+        self.line(x1, y, x1, y1, color);
+    }
+
+    pub fn circle(&mut self, cx: i64, cy: i64, r: i64, color: Rgb) {
+        let steps = (r * 8).max(16);
+        for i in 0..steps {
+            let a = i as f64 / steps as f64 * std::f64::consts::TAU;
+            let x = cx + (a.cos() * r as f64).round() as i64;
+            let y = cy + (a.sin() * r as f64).round() as i64;
+            self.set_pixel(x, y, color);
+        }
+    }
+
+    /// Copy another image into this one, box-filtered down to `dst_w` x `dst_h`.
+    pub fn blit_scaled(&mut self, src: &Canvas, x: i64, y: i64, dst_w: u32, dst_h: u32) {
+        let (src_w, src_h) = (src.width, src.height);
+        for dy in 0..dst_h {
+            let sy0 = dy * src_h / dst_h;
+            let sy1 = ((dy + 1) * src_h / dst_h).max(sy0 + 1);
+            for dx in 0..dst_w {
+                let sx0 = dx * src_w / dst_w;
+                let sx1 = ((dx + 1) * src_w / dst_w).max(sx0 + 1);
+                let mut sum = [0u32; 3];
+                let mut n = 0u32;
+                for sy in sy0..sy1 {
+                    for sx in sx0..sx1 {
+                        let idx = (sy * src_w + sx) as usize * 3;
+                        for (acc, &v) in sum.iter_mut().zip(&src.data[idx..idx + 3]) {
+                            *acc += v as u32;
+                        }
+                        n += 1;
+                    }
+                }
+                let px = [(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8];
+                self.set_pixel(x + dx as i64, y + dy as i64, px);
+            }
+        }
+    }
+
+    /// Draw text in the built-in 5x7 font. Lowercase letters render as uppercase.
+    pub fn text(&mut self, x: i64, y: i64, text: &str, scale: u32, color: Rgb) {
+        let advance = ((GLYPH_WIDTH + 1) * scale) as i64;
+        for (i, ch) in text.chars().enumerate() {
+            let rows = glyph(ch.to_ascii_uppercase());
+            let gx = x + i as i64 * advance;
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        self.fill_rect(
+                            gx + (col * scale) as i64,
+                            y + (row as u32 * scale) as i64,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width in pixels of `text` drawn at `scale`.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * (GLYPH_WIDTH + 1) * scale
+}
+
+/// 5x7 bitmaps, one byte per row, MSB-aligned to bit 4.
+fn glyph(ch: char) -> [u8; 7] {
+    match ch {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        _ => [0x00; 7],
+    }
+}
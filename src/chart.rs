@@ -0,0 +1,299 @@
+use crate::color;
+use crate::geometry::{Homography, Point};
+use crate::yuv::{self, YuvMatrix};
+
+/// One patch of a reference chart with its published sRGB value.
+pub struct Patch {
+    pub name: &'static str,
+    pub srgb: [u8; 3],
+}
+
+/// A reference chart: a `cols` × `rows` grid of patches, stored row-major.
+pub struct Layout {
+    pub name: &'static str,
+    pub cols: usize,
+    pub rows: usize,
+    pub patches: &'static [Patch],
+}
+
+/// 24-patch ColorChecker Classic, dark skin at top-left, black at bottom-right.
+pub const COLORCHECKER: Layout = Layout {
+    name: "ColorChecker",
+    cols: 6,
+    rows: 4,
+    patches: &COLORCHECKER_PATCHES,
+};
+
+/// Top section of an SMPTE colour bar card: seven 75% bars, grey at the left.
+pub const SMPTE_BARS: Layout = Layout {
+    name: "SMPTE colour bars",
+    cols: 7,
+    rows: 1,
+    patches: &SMPTE_BAR_PATCHES,
+};
+
+const COLORCHECKER_PATCHES: [Patch; 24] = [
+    patch("Dark skin", [115, 82, 68]),
+    patch("Light skin", [194, 150, 130]),
+    patch("Blue sky", [98, 122, 157]),
+    patch("Foliage", [87, 108, 67]),
+    patch("Blue flower", [133, 128, 177]),
+    patch("Bluish green", [103, 189, 170]),
+    patch("Orange", [214, 126, 44]),
+    patch("Purplish blue", [80, 91, 166]),
+    patch("Moderate red", [193, 90, 99]),
+    patch("Purple", [94, 60, 108]),
+    patch("Yellow green", [157, 188, 64]),
+    patch("Orange yellow", [224, 163, 46]),
+    patch("Blue", [56, 61, 150]),
+    patch("Green", [70, 148, 73]),
+    patch("Red", [175, 54, 60]),
+    patch("Yellow", [231, 199, 31]),
+    patch("Magenta", [187, 86, 149]),
+    patch("Cyan", [8, 133, 161]),
+    patch("White", [243, 243, 242]),
+    patch("Neutral 8", [200, 200, 200]),
+    patch("Neutral 6.5", [160, 160, 160]),
+    patch("Neutral 5", [122, 122, 121]),
+    patch("Neutral 3.5", [85, 85, 85]),
+    patch("Black", [52, 52, 52]),
+];
+
+const SMPTE_BAR_PATCHES: [Patch; 7] = [
+    patch("Grey", [191, 191, 191]),
+    patch("Yellow", [191, 191, 0]),
+    patch("Cyan", [0, 191, 191]),
+    patch("Green", [0, 191, 0]),
+    patch("Magenta", [191, 0, 191]),
+    patch("Red", [191, 0, 0]),
+    patch("Blue", [0, 0, 191]),
+];
+
+const fn patch(name: &'static str, srgb: [u8; 3]) -> Patch {
+    Patch { name, srgb }
+}
+
+/// Fraction of each cell (per axis) that is sampled, centred, to stay clear of patch edges.
+const SAMPLE_FRACTION: f64 = 0.5;
+/// Sample points per axis within each patch.
+const SAMPLES_PER_AXIS: usize = 16;
+/// Luma standard deviation above which a patch is probably not where we think it is.
+const NON_UNIFORM_Y_STDDEV: f64 = 8.0;
+
+/// Mean Y/Cb/Cr code values measured inside one patch.
+pub struct PatchSample {
+    pub y: f64,
+    pub cb: f64,
+    pub cr: f64,
+    pub y_stddev: f64,
+    /// Sample points that fell inside the frame; fewer than [`PatchSample::POINTS`] if
+    /// the patch is partly or entirely outside it.
+    pub count: usize,
+    /// Sampled region in frame coordinates (TL, TR, BR, BL).
+    pub region: [Point; 4],
+}
+
+impl PatchSample {
+    /// Sample points in a patch that lies wholly inside the frame.
+    pub const POINTS: usize = SAMPLES_PER_AXIS * SAMPLES_PER_AXIS;
+
+    pub fn is_uniform(&self) -> bool {
+        self.count > 0 && self.y_stddev <= NON_UNIFORM_Y_STDDEV
+    }
+}
+
+/// Names of the patches whose sampled area leaves the frame, wholly or in part. Such a
+/// placement can't be scored: a clipped patch is averaged over whatever is left of it,
+/// and a missing one would read as black.
+pub fn patches_outside(layout: &Layout, samples: &[PatchSample]) -> Vec<&'static str> {
+    layout
+        .patches
+        .iter()
+        .zip(samples)
+        .filter(|(_, s)| s.count < PatchSample::POINTS)
+        .map(|(p, _)| p.name)
+        .collect()
+}
+
+pub struct MatrixScore {
+    pub matrix: &'static YuvMatrix,
+    /// ΔE2000 per patch, same order as [`Layout::patches`].
+    pub delta_e: Vec<f64>,
+    pub mean: f64,
+    pub max: f64,
+}
+
+pub struct ChartReport {
+    pub samples: Vec<PatchSample>,
+    /// One score per candidate matrix, in [`yuv::CANDIDATE_MATRICES`] order.
+    pub scores: Vec<MatrixScore>,
+}
+
+impl ChartReport {
+    /// The candidate matrix with the lowest mean ΔE.
+    pub fn best(&self) -> &MatrixScore {
+        self.scores
+            .iter()
+            .min_by(|a, b| a.mean.total_cmp(&b.mean))
+            .expect("at least one candidate matrix")
+    }
+}
+
+/// Measure each patch of a chart whose patch grid maps onto `chart`
+/// (unit square = outer corners of the layout's patch area).
+pub fn sample_patches(
+    layout: &Layout,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    chart: &Homography,
+) -> Vec<PatchSample> {
+    let w = width as usize;
+    let h = height as usize;
+    let uv_plane = &data[w * h..];
+
+    let (cols, rows) = (layout.cols as f64, layout.rows as f64);
+    let mut samples = Vec::with_capacity(layout.patches.len());
+    for row in 0..layout.rows {
+        for col in 0..layout.cols {
+            let margin = (1.0 - SAMPLE_FRACTION) / 2.0;
+            let u0 = (col as f64 + margin) / cols;
+            let u1 = (col as f64 + 1.0 - margin) / cols;
+            let v0 = (row as f64 + margin) / rows;
+            let v1 = (row as f64 + 1.0 - margin) / rows;
+
+            let (mut sy, mut syy, mut scb, mut scr, mut count) = (0.0, 0.0, 0.0, 0.0, 0);
+            for i in 0..SAMPLES_PER_AXIS {
+                for j in 0..SAMPLES_PER_AXIS {
+                    let u = u0 + (u1 - u0) * (j as f64 + 0.5) / SAMPLES_PER_AXIS as f64;
+                    let v = v0 + (v1 - v0) * (i as f64 + 0.5) / SAMPLES_PER_AXIS as f64;
+                    let p = chart.map(u, v);
+                    if p.x < 0.0 || p.y < 0.0 || p.x >= width as f64 || p.y >= height as f64 {
+                        continue;
+                    }
+                    let (px, py) = (p.x as usize, p.y as usize);
+                    let y = data[py * w + px] as f64;
+                    let uv_idx = (py / 2) * w + (px / 2) * 2;
+                    sy += y;
+                    syy += y * y;
+                    scb += uv_plane[uv_idx] as f64;
+                    scr += uv_plane[uv_idx + 1] as f64;
+                    count += 1;
+                }
+            }
+
+            let n = count.max(1) as f64;
+            let mean_y = sy / n;
+            samples.push(PatchSample {
+                y: mean_y,
+                cb: scb / n,
+                cr: scr / n,
+                y_stddev: (syy / n - mean_y * mean_y).max(0.0).sqrt(),
+                count,
+                region: [
+                    chart.map(u0, v0),
+                    chart.map(u1, v0),
+                    chart.map(u1, v1),
+                    chart.map(u0, v1),
+                ],
+            });
+        }
+    }
+    samples
+}
+
+/// Decode each sampled patch under every candidate matrix and score it against the reference.
+pub fn evaluate(layout: &Layout, samples: Vec<PatchSample>, full_range: bool) -> ChartReport {
+    let scores = yuv::CANDIDATE_MATRICES
+        .iter()
+        .map(|&matrix| {
+            let delta_e: Vec<f64> = samples
+                .iter()
+                .zip(layout.patches)
+                .map(|(s, p)| {
+                    let rgb = yuv::ycbcr_to_rgb(s.y, s.cb, s.cr, matrix, full_range);
+                    let reference = p.srgb.map(|c| c as f64);
+                    color::delta_e_2000(color::srgb_to_lab(rgb), color::srgb_to_lab(reference))
+                })
+                .collect();
+            let mean = delta_e.iter().sum::<f64>() / delta_e.len() as f64;
+            let max = delta_e.iter().copied().fold(0.0, f64::max);
+            MatrixScore {
+                matrix,
+                delta_e,
+                mean,
+                max,
+            }
+        })
+        .collect();
+
+    ChartReport { samples, scores }
+}
+
+/// Parse "x1,y1,x2,y2,x3,y3,x4,y4" (TL, TR, BR, BL) into chart corners.
+pub fn parse_corners(s: &str) -> Option<[Point; 4]> {
+    let values: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if values.len() != 8 {
+        return None;
+    }
+    let p = |i: usize| Point {
+        x: values[i * 2],
+        y: values[i * 2 + 1],
+    };
+    Some([p(0), p(1), p(2), p(3)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mid-grey NV12 frame.
+    fn grey(width: u32, height: u32) -> Vec<u8> {
+        vec![128; (width * height * 3 / 2) as usize]
+    }
+
+    fn chart(corners: [(f64, f64); 4]) -> Homography {
+        Homography::from_unit_square(corners.map(|(x, y)| Point { x, y }))
+    }
+
+    #[test]
+    fn a_chart_inside_the_frame_has_no_patches_outside() {
+        let samples = sample_patches(
+            &COLORCHECKER,
+            &grey(64, 48),
+            64,
+            48,
+            &chart([(2.0, 2.0), (62.0, 2.0), (62.0, 42.0), (2.0, 42.0)]),
+        );
+        assert!(samples.iter().all(|s| s.count == PatchSample::POINTS));
+        assert!(patches_outside(&COLORCHECKER, &samples).is_empty());
+    }
+
+    #[test]
+    fn a_partly_clipped_patch_counts_as_outside() {
+        // The right column is sampled from x = 87.5 to 95.8; the frame ends at 92
+        let samples = sample_patches(
+            &COLORCHECKER,
+            &grey(92, 48),
+            92,
+            48,
+            &chart([(0.0, 0.0), (100.0, 0.0), (100.0, 40.0), (0.0, 40.0)]),
+        );
+        let right_column = [5, 11, 17, 23];
+        for (i, sample) in samples.iter().enumerate() {
+            if right_column.contains(&i) {
+                assert!(sample.count > 0 && sample.count < PatchSample::POINTS);
+            } else {
+                assert_eq!(sample.count, PatchSample::POINTS);
+            }
+        }
+        assert_eq!(
+            patches_outside(&COLORCHECKER, &samples),
+            ["Bluish green", "Orange yellow", "Cyan", "Black"]
+        );
+    }
+}
@@ -1,93 +1,784 @@
-use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+use crate::chart::{self, Layout};
+use crate::controls::{CameraControl, ControlSetting, ControlValue};
+use crate::geometry::Point;
+use crate::output;
+use crate::settle::{self, SettlePolicy};
+
+/// What to do, with the global options that apply to it.
+pub struct Invocation {
+    /// Print results as JSON instead of text.
+    pub json: bool,
+    /// Leave out banners, progress and guidance.
+    pub quiet: bool,
+    pub command: Command,
+}
 
 pub enum Command {
-    Enumerate,
-    CaptureTest {
-        device_index: usize,
-        resolution: Option<(u32, u32)>,
-        mirror: bool,
-        save_raw: bool,
+    /// Enumerate every device, or only the one given with `--device`.
+    List {
+        device: Option<DeviceSelector>,
+    },
+    /// Look for contradictions between the driver, the descriptors and the quirks database.
+    Check {
+        device: Option<DeviceSelector>,
+    },
+    /// Save the enumeration to `file`.
+    Snapshot {
+        file: PathBuf,
+        force: bool,
+        device: Option<DeviceSelector>,
+    },
+    /// Compare a saved enumeration with the live one, or with a second saved one.
+    Diff {
+        baseline: PathBuf,
+        current: Option<PathBuf>,
+        device: Option<DeviceSelector>,
     },
+    CaptureTest(Box<CaptureTestArgs>),
+    Decode(Box<DecodeArgs>),
+    Record(Box<RecordArgs>),
     ForceMatrix {
         matrix: MatrixChoice,
-        device_index: usize,
+        device: DeviceSelector,
+        /// Also capture and convert a frame through the OS's colour converter.
+        capture: Option<ForceCapture>,
     },
+    AddQuirk(AddQuirkArgs),
+    Completions(Shell),
 }
 
-#[derive(Clone, Copy)]
+/// A capture verdict to record in the user's quirks file.
+pub struct AddQuirkArgs {
+    pub device: DeviceSelector,
+    /// The matrix the firmware really encodes with.
+    pub matrix: MatrixChoice,
+    /// The real range, if known: `Some(true)` for full.
+    pub full_range: Option<bool>,
+    /// Key the entry on VID:PID alone so it covers every firmware revision.
+    pub any_revision: bool,
+    pub note: Option<String>,
+}
+
+pub struct CaptureTestArgs {
+    pub device: DeviceSelector,
+    pub resolution: Option<(u32, u32)>,
+    pub save_raw: bool,
+    /// Also save every captured frame, before averaging, as a Y4M sequence.
+    pub save_y4m: bool,
+    /// Frames to average before decoding; more than one also measures temporal noise.
+    pub frames: usize,
+    pub settle: SettlePolicy,
+    pub mjpg: bool,
+    /// Camera controls to pin while capturing, in `CameraControl::ALL` order.
+    pub controls: Vec<ControlSetting>,
+    /// Capture from every device instead of one.
+    pub all_devices: bool,
+    /// Capture every raw YUV resolution instead of one.
+    pub all_formats: bool,
+    /// Limits `all_formats` to some pixel formats or sizes.
+    pub formats: Option<FormatFilter>,
+    /// Also capture RGB converted by the OS and match it against our decodes.
+    pub compare_os: bool,
+    pub analysis: AnalysisArgs,
+}
+
+/// A raw clip to record and analyse frame by frame.
+pub struct RecordArgs {
+    pub device: DeviceSelector,
+    pub resolution: Option<(u32, u32)>,
+    pub duration: Duration,
+    pub settle: SettlePolicy,
+    pub controls: Vec<ControlSetting>,
+    pub output_dir: Option<PathBuf>,
+    pub name_template: Option<String>,
+    pub force: bool,
+}
+
+/// `force --capture`: where the override test's images go.
+pub struct ForceCapture {
+    pub resolution: Option<(u32, u32)>,
+    pub output_dir: Option<PathBuf>,
+    pub force: bool,
+}
+
+/// A raw frame saved by an earlier capture, to analyse again.
+pub struct DecodeArgs {
+    pub file: PathBuf,
+    /// Required for raw NV12; a Y4M file carries its own.
+    pub size: Option<(u32, u32)>,
+    /// `None` uses the Y4M file's `XCOLORRANGE`, or limited.
+    pub full_range: Option<bool>,
+    /// 0-based frame of a Y4M sequence to decode; `None` averages them all.
+    pub frame: Option<usize>,
+    pub analysis: AnalysisArgs,
+}
+
+/// What to do with a frame once it is in memory, and where the results go.
+pub struct AnalysisArgs {
+    pub mirror: bool,
+    pub grid: bool,
+    pub scopes: bool,
+    pub chart: Option<ChartArg>,
+    /// Also write everything into one self-contained HTML file.
+    pub report: bool,
+    /// Where output files go; the current directory if `None`.
+    pub output_dir: Option<PathBuf>,
+    /// File name template; see [`crate::output`] for the placeholders.
+    pub name_template: Option<String>,
+    /// Overwrite existing output files.
+    pub force: bool,
+}
+
+/// `--formats`: pixel formats and sizes to capture with `--all-formats`. An empty list
+/// of either kind allows all of that kind.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatFilter {
+    pub pixel_formats: Vec<String>,
+    pub resolutions: Vec<(u32, u32)>,
+}
+
+impl FormatFilter {
+    pub fn matches(&self, pixel_format: &str, resolution: (u32, u32)) -> bool {
+        (self.pixel_formats.is_empty()
+            || self
+                .pixel_formats
+                .iter()
+                .any(|f| f.eq_ignore_ascii_case(pixel_format)))
+            && (self.resolutions.is_empty() || self.resolutions.contains(&resolution))
+    }
+}
+
+/// Which camera to use, from `--device`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceSelector {
+    /// 0-based position in enumeration order.
+    Index(usize),
+    /// A friendly name substring, device path, symbolic link or VID:PID[:bcdDevice].
+    Query(String),
+}
+
+/// A reference chart to measure in the captured frame.
+pub struct ChartArg {
+    pub layout: &'static Layout,
+    /// Outer corners of the chart's patch grid (TL, TR, BR, BL); `None` to detect it.
+    pub corners: Option<[Point; 4]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
 pub enum MatrixChoice {
     Bt601,
     Bt709,
 }
 
-pub fn parse_args() -> anyhow::Result<Command> {
-    let args: Vec<String> = env::args().skip(1).collect();
+impl MatrixChoice {
+    /// As the OS backends name it.
+    pub fn name(self) -> &'static str {
+        match self {
+            MatrixChoice::Bt601 => "BT.601",
+            MatrixChoice::Bt709 => "BT.709",
+        }
+    }
+}
+
+/// Upper bound for --frames; every frame is held in memory until averaged.
+pub const MAX_FRAMES: u64 = 120;
+
+/// Upper bound for `record --duration`; 1080p30 fills about 93 MB per second.
+const MAX_RECORD_SECONDS: u64 = 600;
+
+const EXAMPLES: &str = "\
+Examples:
+  webcam-colorspace                                # list all cameras
+  webcam-colorspace --json > cameras.json          # same, machine-readable
+  webcam-colorspace check                          # flag driver/descriptor contradictions
+  webcam-colorspace snapshot before.json           # save the enumeration...
+  webcam-colorspace diff before.json               # ...and after an update, see what changed
+  webcam-colorspace capture                        # capture highest-res NV12
+  webcam-colorspace capture 1280x720 --mirror      # 720p, mirrored (selfie view)
+  webcam-colorspace capture --save-raw --frames 16 # average 16 frames, keep the raw bytes
+  webcam-colorspace capture --save-y4m --frames 30 # keep all 30 frames as a Y4M clip
+  webcam-colorspace capture --mjpg                 # inspect MJPG frame markers
+  webcam-colorspace capture --grid --scopes        # contact sheet, waveform + vectorscope
+  webcam-colorspace capture --colorchecker auto    # score each matrix against a chart
+  webcam-colorspace capture --report --scopes      # one HTML file to attach to a bug report
+  webcam-colorspace capture --compare-os           # Windows: which matrix does the OS use?
+  webcam-colorspace capture --exposure 156 --white-balance 5000 --gain 0
+  webcam-colorspace -d logitech capture 640x480    # by name
+  webcam-colorspace -d 046d:0825 capture           # by USB ID
+  webcam-colorspace capture --all-devices --all-formats
+  webcam-colorspace capture --output-dir captures --name '{device}_{timestamp}_{output}'
+  webcam-colorspace decode capture_raw.nv12 --size 1280x720 --colorbars auto
+  webcam-colorspace decode capture_raw.y4m --frame 1 --grid
+  webcam-colorspace record 1280x720 --duration 30  # watch for mid-stream matrix switches
+  webcam-colorspace -d 2 force bt601               # force BT.601 on device 2
+  webcam-colorspace force bt709 1280x720 --capture # does the OS converter honour it?
+  webcam-colorspace -d 2 add-quirk bt709 limited   # device 2 really encodes BT.709
+  webcam-colorspace completions bash > /etc/bash_completion.d/webcam-colorspace";
+
+/// Camera colorspace diagnostic tool: compares the YUV matrix and range a camera's
+/// driver advertises with what its firmware actually encodes.
+#[derive(Parser)]
+#[command(name = "webcam-colorspace", version, after_help = EXAMPLES)]
+struct Cli {
+    /// Print results as JSON (list and check)
+    #[arg(long, global = true, help_heading = "Global options")]
+    json: bool,
+
+    /// Only print results: no banners, progress or guidance
+    #[arg(short, long, global = true, help_heading = "Global options")]
+    quiet: bool,
+
+    /// Camera to use: device number (1-based, default 1), part of its name, device path
+    /// (/dev/video0, /dev/v4l/by-id/...), Windows symbolic link, or USB VID:PID[:bcdDevice]
+    #[arg(short, long, global = true, help_heading = "Global options", value_name = "DEV",
+          value_parser = device_selector)]
+    device: Option<DeviceSelector>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Enumerate devices with their formats, colorspace attributes and controls (default)
+    List,
+    /// Report drivers that contradict the UVC descriptors or a known quirk; exits with
+    /// status 1 if any does
+    Check,
+    /// Save the enumeration as JSON, to compare against after a driver or firmware update
+    Snapshot {
+        file: PathBuf,
+        /// Overwrite FILE if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Compare the enumeration with a snapshot: added and removed formats, changed
+    /// colorspace attributes; exits with status 1 if anything changed
+    Diff {
+        /// Snapshot saved earlier with `snapshot` (or `--json`)
+        baseline: PathBuf,
+        /// A second snapshot to compare instead of the live enumeration
+        current: Option<PathBuf>,
+    },
+    /// Capture a frame and decode it with BT.601 and BT.709
+    #[command(long_flag_alias = "capture-test")]
+    Capture(CaptureOpts),
+    /// Decode a raw NV12 frame or Y4M clip saved by `capture --save-raw`/`--save-y4m`
+    Decode(DecodeOpts),
+    /// Record raw frames to a Y4M file and flag sudden changes in colour processing;
+    /// exits with status 1 if any
+    Record(RecordOpts),
+    /// Override the YUV matrix on the media type
+    #[command(long_flag_alias = "force-matrix")]
+    Force {
+        #[arg(value_enum, ignore_case = true)]
+        matrix: MatrixChoice,
+        /// Resolution for --capture, e.g. 1280x720 [default: highest available]
+        #[arg(value_name = "WxH", value_parser = resolution, requires = "capture")]
+        resolution: Option<(u32, u32)>,
+        /// Capture a frame with the override and convert it with the OS's Video Processor
+        /// MFT, to see whether the OS honours it (Windows)
+        #[arg(long)]
+        capture: bool,
+        /// Directory for the --capture images [default: current directory]
+        #[arg(long, value_name = "DIR", requires = "capture")]
+        output_dir: Option<PathBuf>,
+        /// Overwrite existing --capture images
+        #[arg(long, requires = "capture")]
+        force: bool,
+    },
+    /// Record the matrix (and range) a capture showed the camera really uses in the
+    /// user's quirks file, keyed by USB VID:PID:bcdDevice
+    #[command(long_flag_alias = "add-quirk")]
+    AddQuirk {
+        #[arg(value_enum, ignore_case = true)]
+        matrix: MatrixChoice,
+        #[arg(value_enum, ignore_case = true)]
+        range: Option<RangeChoice>,
+        /// Key the entry on VID:PID alone so it covers every firmware revision
+        #[arg(long)]
+        any_revision: bool,
+        /// Free text stored with the entry
+        #[arg(long, value_name = "TEXT")]
+        note: Option<String>,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RangeChoice {
+    Full,
+    Limited,
+}
+
+#[derive(Args)]
+struct CaptureOpts {
+    /// Resolution to capture, e.g. 1280x720 [default: highest available]
+    #[arg(value_name = "WxH", value_parser = resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Also save the raw frame: capture_raw.nv12, or capture_raw.jpg with --mjpg
+    #[arg(long)]
+    save_raw: bool,
+
+    /// Also save every captured frame as capture_raw.y4m, playable in mpv or ffplay
+    #[arg(long)]
+    save_y4m: bool,
+
+    /// Average F frames in YUV and report temporal noise
+    #[arg(long, value_name = "F", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..=MAX_FRAMES))]
+    frames: u64,
+
+    /// 'auto' waits for exposure and white balance to settle; N skips N frames instead
+    #[arg(long, value_name = "auto|N", value_parser = settle_skip)]
+    settle: Option<SettleSkip>,
+
+    /// Largest change in mean Y/Cb/Cr (code values) that counts as settled [default: 1.0]
+    #[arg(long, value_name = "T", value_parser = positive)]
+    settle_tolerance: Option<f64>,
+
+    /// Settled frames in a row required [default: 3]
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u64).range(1..))]
+    settle_frames: Option<u64>,
+
+    /// Seconds to wait for the camera to settle at most [default: 5]
+    #[arg(long, value_name = "S", value_parser = positive)]
+    settle_timeout: Option<f64>,
+
+    /// Capture MJPG and report the colorspace the JPEG markers claim
+    #[arg(long)]
+    mjpg: bool,
+
+    #[command(flatten)]
+    controls: ControlOpts,
+
+    /// Repeat the test for every device
+    #[arg(long)]
+    all_devices: bool,
+
+    /// Repeat the test for every raw YUV resolution, writing into <device>/<format>_<WxH>/
+    #[arg(long)]
+    all_formats: bool,
+
+    /// Pixel formats and sizes --all-formats captures, e.g. NV12,1280x720,1920x1080
+    #[arg(long, value_name = "LIST", value_parser = format_filter)]
+    formats: Option<FormatFilter>,
+
+    /// Also capture RGB converted by the OS and find which of our decodes it matches
+    #[cfg(windows)]
+    #[arg(long)]
+    compare_os: bool,
+
+    #[command(flatten)]
+    analysis: AnalysisOpts,
+}
+
+#[derive(Args)]
+struct ControlOpts {
+    /// Pin exposure: log2 seconds on Windows (-6 = 1/64 s), 100 µs steps on Linux (156 = 15.6 ms)
+    #[arg(long, value_name = "auto|E", value_parser = control_value,
+          allow_negative_numbers = true)]
+    exposure: Option<ControlValue>,
+
+    /// Pin the white balance temperature in kelvin (e.g. 4600)
+    #[arg(long, value_name = "auto|K", value_parser = control_value,
+          allow_negative_numbers = true)]
+    white_balance: Option<ControlValue>,
+
+    /// Pin gain, in driver units
+    #[arg(long, value_name = "auto|G", value_parser = control_value,
+          allow_negative_numbers = true)]
+    gain: Option<ControlValue>,
+
+    /// Pin backlight compensation, in driver units
+    #[arg(long, value_name = "B", value_parser = control_value,
+          allow_negative_numbers = true)]
+    backlight: Option<ControlValue>,
+}
+
+#[derive(Args)]
+struct RecordOpts {
+    /// Resolution to record, e.g. 1280x720 [default: highest available]
+    #[arg(value_name = "WxH", value_parser = resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Seconds to record
+    #[arg(long, value_name = "S", default_value_t = 10,
+          value_parser = clap::value_parser!(u64).range(1..=MAX_RECORD_SECONDS))]
+    duration: u64,
+
+    /// 'auto' waits for exposure and white balance to settle first; N skips N frames
+    /// instead (0 records from the first frame)
+    #[arg(long, value_name = "auto|N", value_parser = settle_skip)]
+    settle: Option<SettleSkip>,
+
+    #[command(flatten)]
+    controls: ControlOpts,
+
+    /// Directory for the recording, created if needed [default: current directory]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// File name without extension, from {device}, {resolution}, {timestamp}, {matrix},
+    /// {range} and {output} (record, record_stats)
+    #[arg(long = "name", value_name = "TEMPLATE", value_parser = name_template)]
+    name_template: Option<String>,
+
+    /// Overwrite existing files
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct DecodeOpts {
+    /// Raw NV12 file, e.g. capture_raw.nv12, or a Y4M file
+    file: PathBuf,
+
+    /// Frame size the file was captured at, e.g. 1280x720 (not needed for Y4M)
+    #[arg(long, value_name = "WxH", value_parser = resolution)]
+    size: Option<(u32, u32)>,
+
+    /// Range to decode with [default: the Y4M file's XCOLORRANGE, or limited]
+    #[arg(long, value_enum)]
+    range: Option<RangeChoice>,
+
+    /// Decode frame N of a Y4M sequence instead of averaging all of them
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    frame: Option<u64>,
+
+    #[command(flatten)]
+    analysis: AnalysisOpts,
+}
+
+#[derive(Args)]
+struct AnalysisOpts {
+    /// Flip the decoded images horizontally (selfie view)
+    #[arg(long)]
+    mirror: bool,
+
+    /// Also decode every matrix x range combination into a contact sheet
+    #[arg(long)]
+    grid: bool,
+
+    /// Render waveform, RGB parade and vectorscope images
+    #[arg(long)]
+    scopes: bool,
+
+    /// Score each matrix against a ColorChecker Classic: 'auto', or the outer corners of
+    /// its patch grid, x1,y1,x2,y2,x3,y3,x4,y4 (top-left dark skin, clockwise)
+    #[arg(long, value_name = "auto|CORNERS", value_parser = corners,
+          conflicts_with = "colorbars")]
+    colorchecker: Option<Corners>,
+
+    /// The same against the 75% bars of an SMPTE colour bar card (grey bar top-left)
+    #[arg(long, value_name = "auto|CORNERS", value_parser = corners)]
+    colorbars: Option<Corners>,
+
+    /// Write a self-contained HTML report with all of the above and a verdict
+    #[arg(long)]
+    report: bool,
+
+    /// Directory for output files, created if needed [default: current directory]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// File name without extension, from {device}, {resolution}, {timestamp}, {matrix},
+    /// {range} and {output} (capture_bt601, scope_waveform, ...)
+    #[arg(long = "name", value_name = "TEMPLATE", value_parser = name_template)]
+    name_template: Option<String>,
+
+    /// Overwrite existing output files
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Clone, Copy)]
+enum SettleSkip {
+    Auto,
+    Frames(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Corners {
+    Auto,
+    Given([Point; 4]),
+}
 
-    if args.is_empty() {
-        return Ok(Command::Enumerate);
+impl Corners {
+    fn points(self) -> Option<[Point; 4]> {
+        match self {
+            Corners::Auto => None,
+            Corners::Given(points) => Some(points),
+        }
     }
+}
+
+/// Parse the command line; exits with a usage error if it is invalid.
+pub fn parse_args() -> Invocation {
+    parse_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+}
 
-    match args[0].as_str() {
-        "-h" | "--help" => {
-            print_usage();
-            std::process::exit(0);
+fn parse_from<I, T>(args: I) -> Result<Invocation, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    Cli::try_parse_from(args)?
+        .into_invocation()
+        .map_err(|e| e.format(&mut Cli::command()))
+}
+
+/// Write the completion script for `shell` to stdout.
+pub fn print_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+impl Cli {
+    /// Checks clap can't express, and conversion to what `main` works with.
+    fn into_invocation(self) -> Result<Invocation, clap::Error> {
+        let conflict = |message: &str| Err(clap::Error::raw(ErrorKind::ArgumentConflict, message));
+        let command = self.command.unwrap_or(CliCommand::List);
+        if self.json && !matches!(command, CliCommand::List | CliCommand::Check) {
+            return conflict("--json only applies to list and check.");
         }
-        "--capture-test" => {
-            let mut device_index = 0usize;
-            let mut resolution = None;
-            let mut mirror = false;
-            let mut save_raw = false;
+        let selected = self.device.clone().unwrap_or(DeviceSelector::Index(0));
 
-            for arg in &args[1..] {
-                if arg == "--mirror" {
-                    mirror = true;
-                } else if arg == "--save-raw" {
-                    save_raw = true;
-                } else if let Some(res) = parse_resolution(arg) {
-                    resolution = Some(res);
-                } else if let Ok(n) = arg.parse::<usize>() {
-                    if n == 0 {
-                        anyhow::bail!("Device number must be >= 1 (1-based index).");
-                    }
-                    device_index = n - 1;
-                } else {
-                    anyhow::bail!(
-                        "Unknown argument '{}' for --capture-test. Expected a device number, WxH resolution, --mirror, or --save-raw.",
-                        arg
+        let command = match command {
+            CliCommand::List => Command::List {
+                device: self.device,
+            },
+            CliCommand::Check => Command::Check {
+                device: self.device,
+            },
+            CliCommand::Snapshot { file, force } => Command::Snapshot {
+                file,
+                force,
+                device: self.device,
+            },
+            CliCommand::Diff { baseline, current } => {
+                if current.is_some() && self.device.is_some() {
+                    return conflict(
+                        "--device selects a live camera; it can't be combined with a second snapshot.",
                     );
                 }
+                Command::Diff {
+                    baseline,
+                    current,
+                    device: self.device,
+                }
             }
+            CliCommand::Capture(opts) => {
+                let adaptive_options = opts.settle_tolerance.is_some()
+                    || opts.settle_frames.is_some()
+                    || opts.settle_timeout.is_some();
+                let settle = match opts.settle.unwrap_or(SettleSkip::Auto) {
+                    SettleSkip::Frames(_) if adaptive_options => {
+                        return conflict(
+                            "--settle N skips a fixed number of frames; it can't be combined with --settle-tolerance, --settle-frames or --settle-timeout.",
+                        )
+                    }
+                    SettleSkip::Frames(n) => SettlePolicy::Fixed(n),
+                    SettleSkip::Auto => SettlePolicy::Adaptive {
+                        tolerance: opts.settle_tolerance.unwrap_or(settle::DEFAULT_TOLERANCE),
+                        stable_frames: opts
+                            .settle_frames
+                            .map_or(settle::DEFAULT_STABLE_FRAMES, |n| n as usize),
+                        timeout: opts
+                            .settle_timeout
+                            .map_or(settle::DEFAULT_TIMEOUT, Duration::from_secs_f64),
+                    },
+                };
 
-            Ok(Command::CaptureTest {
-                device_index,
-                resolution,
-                mirror,
-                save_raw,
-            })
-        }
-        "--force-matrix" => {
-            if args.len() < 2 {
-                anyhow::bail!("--force-matrix requires a value: bt601 or bt709");
+                let analysis = opts.analysis.into_args();
+                if opts.all_devices && self.device.is_some() {
+                    return conflict("--all-devices can't be combined with --device.");
+                }
+                if opts.all_formats && opts.resolution.is_some() {
+                    return conflict(
+                        "--all-formats captures every resolution; use --formats to pick some.",
+                    );
+                }
+                if opts.formats.is_some() && !opts.all_formats {
+                    return conflict(
+                        "--formats selects what --all-formats captures; add --all-formats.",
+                    );
+                }
+                if opts.mjpg && (opts.all_devices || opts.all_formats) {
+                    return conflict(
+                        "--mjpg can't be combined with --all-devices or --all-formats.",
+                    );
+                }
+                if opts.mjpg && opts.save_y4m {
+                    return conflict(
+                        "--save-y4m saves raw YUV frames; with --mjpg use --save-raw.",
+                    );
+                }
+                // Linux would need libv4l's conversion, which the tool doesn't use
+                #[cfg(windows)]
+                let compare_os = opts.compare_os;
+                #[cfg(not(windows))]
+                let compare_os = false;
+                if opts.mjpg && compare_os {
+                    return conflict(
+                        "--compare-os compares against our decodes of raw YUV; it can't be combined with --mjpg.",
+                    );
+                }
+                if opts.all_formats && analysis.chart.as_ref().is_some_and(|c| c.corners.is_some())
+                {
+                    return conflict(
+                        "Chart corners only fit one resolution; use 'auto' with --all-formats.",
+                    );
+                }
+
+                let controls = opts.controls.into_settings();
+
+                Command::CaptureTest(Box::new(CaptureTestArgs {
+                    device: selected,
+                    resolution: opts.resolution,
+                    save_raw: opts.save_raw,
+                    save_y4m: opts.save_y4m,
+                    frames: opts.frames as usize,
+                    settle,
+                    mjpg: opts.mjpg,
+                    controls,
+                    all_devices: opts.all_devices,
+                    all_formats: opts.all_formats,
+                    formats: opts.formats,
+                    compare_os,
+                    analysis,
+                }))
+            }
+            CliCommand::Decode(opts) => {
+                let y4m = opts
+                    .file
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("y4m"));
+                if !y4m && opts.size.is_none() {
+                    return Err(clap::Error::raw(
+                        ErrorKind::MissingRequiredArgument,
+                        "A raw NV12 file needs --size; only Y4M files carry their own.",
+                    ));
+                }
+                if !y4m && opts.frame.is_some() {
+                    return conflict(
+                        "--frame picks a frame of a Y4M sequence; raw NV12 files hold one.",
+                    );
+                }
+                Command::Decode(Box::new(DecodeArgs {
+                    file: opts.file,
+                    size: opts.size,
+                    full_range: opts.range.map(|r| matches!(r, RangeChoice::Full)),
+                    frame: opts.frame.map(|n| n as usize - 1),
+                    analysis: opts.analysis.into_args(),
+                }))
             }
-            let matrix = match args[1].to_lowercase().as_str() {
-                "bt601" => MatrixChoice::Bt601,
-                "bt709" => MatrixChoice::Bt709,
-                other => anyhow::bail!("Unknown matrix '{}'. Expected: bt601 or bt709", other),
-            };
-            let device_index = parse_optional_device_index(&args, 2)?;
-            Ok(Command::ForceMatrix {
+            CliCommand::Record(opts) => Command::Record(Box::new(RecordArgs {
+                device: selected,
+                resolution: opts.resolution,
+                duration: Duration::from_secs(opts.duration),
+                settle: match opts.settle.unwrap_or(SettleSkip::Auto) {
+                    SettleSkip::Frames(n) => SettlePolicy::Fixed(n),
+                    SettleSkip::Auto => SettlePolicy::default(),
+                },
+                controls: opts.controls.into_settings(),
+                output_dir: opts.output_dir,
+                name_template: opts.name_template,
+                force: opts.force,
+            })),
+            CliCommand::Force {
+                matrix,
+                resolution,
+                capture,
+                output_dir,
+                force,
+            } => Command::ForceMatrix {
+                matrix,
+                device: selected,
+                capture: capture.then_some(ForceCapture {
+                    resolution,
+                    output_dir,
+                    force,
+                }),
+            },
+            CliCommand::AddQuirk {
                 matrix,
-                device_index,
+                range,
+                any_revision,
+                note,
+            } => Command::AddQuirk(AddQuirkArgs {
+                device: selected,
+                matrix,
+                full_range: range.map(|r| matches!(r, RangeChoice::Full)),
+                any_revision,
+                note,
+            }),
+            CliCommand::Completions { shell } => Command::Completions(shell),
+        };
+
+        Ok(Invocation {
+            json: self.json,
+            quiet: self.quiet,
+            command,
+        })
+    }
+}
+
+impl ControlOpts {
+    /// The controls given, in `CameraControl::ALL` order.
+    fn into_settings(self) -> Vec<ControlSetting> {
+        CameraControl::ALL
+            .into_iter()
+            .zip([self.exposure, self.white_balance, self.gain, self.backlight])
+            .filter_map(|(control, value)| {
+                Some(ControlSetting {
+                    control,
+                    value: value?,
+                })
             })
-        }
-        other => {
-            anyhow::bail!("Unknown argument '{}'. Use --help for usage.", other);
+            .collect()
+    }
+}
+
+impl AnalysisOpts {
+    fn into_args(self) -> AnalysisArgs {
+        let chart = match (self.colorchecker, self.colorbars) {
+            (Some(corners), _) => Some((&chart::COLORCHECKER, corners)),
+            (None, Some(corners)) => Some((&chart::SMPTE_BARS, corners)),
+            (None, None) => None,
+        };
+        AnalysisArgs {
+            mirror: self.mirror,
+            grid: self.grid,
+            scopes: self.scopes,
+            chart: chart.map(|(layout, corners)| ChartArg {
+                layout,
+                corners: corners.points(),
+            }),
+            report: self.report,
+            output_dir: self.output_dir,
+            name_template: self.name_template,
+            force: self.force,
         }
     }
 }
 
 /// Parse "WxH" or "WXH" (case-insensitive) into (width, height).
-fn parse_resolution(s: &str) -> Option<(u32, u32)> {
+pub fn parse_resolution(s: &str) -> Option<(u32, u32)> {
     let s_lower = s.to_lowercase();
     let parts: Vec<&str> = s_lower.split('x').collect();
     if parts.len() == 2 {
@@ -98,52 +789,507 @@ fn parse_resolution(s: &str) -> Option<(u32, u32)> {
     None
 }
 
-fn parse_optional_device_index(args: &[String], pos: usize) -> anyhow::Result<usize> {
-    if pos < args.len() {
-        let n: usize = args[pos].parse().map_err(|_| {
-            anyhow::anyhow!(
-                "Invalid device number '{}'. Expected a positive integer.",
-                args[pos]
-            )
-        })?;
-        if n == 0 {
-            anyhow::bail!("Device number must be >= 1 (1-based index).");
-        }
-        Ok(n - 1)
-    } else {
-        Ok(0) // default: first device
-    }
-}
-
-pub fn print_usage() {
-    eprintln!("webcam-colorspace — Camera Colorspace Diagnostic Tool");
-    eprintln!();
-    eprintln!("USAGE:");
-    eprintln!("    webcam-colorspace");
-    eprintln!("        Enumerate devices and show colorspace info");
-    eprintln!();
-    eprintln!("    webcam-colorspace --capture-test [N] [WxH] [--mirror] [--save-raw]");
-    eprintln!("        Capture a frame and decode with BT.601 + BT.709");
-    eprintln!();
-    eprintln!("    webcam-colorspace --force-matrix bt601|bt709 [N]");
-    eprintln!("        Override YUV matrix on the media type");
-    eprintln!();
-    eprintln!("    webcam-colorspace --help");
-    eprintln!("        Show this help");
-    eprintln!();
-    eprintln!("ARGUMENTS:");
-    eprintln!("    N      Device number (1-based, default: 1)");
-    eprintln!("    WxH    Resolution to capture (e.g. 1280x720). Default: highest available.");
-    eprintln!();
-    eprintln!("EXAMPLES:");
-    eprintln!("    webcam-colorspace                            # list all cameras");
-    eprintln!("    webcam-colorspace --capture-test              # capture highest-res NV12");
-    eprintln!("    webcam-colorspace --capture-test 1280x720     # capture at 1280x720");
-    eprintln!(
-        "    webcam-colorspace --capture-test --mirror      # capture mirrored (selfie view)"
-    );
-    eprintln!("    webcam-colorspace --capture-test --save-raw    # also save raw NV12 bytes");
-    eprintln!("    webcam-colorspace --capture-test 2 640x480    # device 2, 640x480");
-    eprintln!("    webcam-colorspace --force-matrix bt709        # force BT.709 on device 1");
-    eprintln!("    webcam-colorspace --force-matrix bt601 2      # force BT.601 on device 2");
+fn resolution(value: &str) -> Result<(u32, u32), String> {
+    parse_resolution(value).ok_or_else(|| "expected WxH, e.g. 1280x720".to_string())
+}
+
+fn positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err("expected a positive number".to_string()),
+    }
+}
+
+fn settle_skip(value: &str) -> Result<SettleSkip, String> {
+    if value == "auto" {
+        return Ok(SettleSkip::Auto);
+    }
+    value
+        .parse::<usize>()
+        .map(SettleSkip::Frames)
+        .map_err(|_| "expected 'auto' or a number of frames to skip".to_string())
+}
+
+/// A control option's value: 'auto' or an integer in the driver's units.
+fn control_value(value: &str) -> Result<ControlValue, String> {
+    if value == "auto" {
+        return Ok(ControlValue::Auto);
+    }
+    value
+        .parse::<i32>()
+        .map(ControlValue::Manual)
+        .map_err(|_| "expected 'auto' or an integer".to_string())
+}
+
+fn corners(value: &str) -> Result<Corners, String> {
+    if value == "auto" {
+        return Ok(Corners::Auto);
+    }
+    chart::parse_corners(value)
+        .map(Corners::Given)
+        .ok_or_else(|| {
+            "expected 'auto' or 8 comma-separated numbers: x1,y1,x2,y2,x3,y3,x4,y4".to_string()
+        })
+}
+
+fn name_template(value: &str) -> Result<String, String> {
+    output::check_template(value)
+        .map(|()| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// The value of `--formats`: comma-separated pixel formats and WxH sizes.
+fn format_filter(value: &str) -> Result<FormatFilter, String> {
+    let mut filter = FormatFilter {
+        pixel_formats: Vec::new(),
+        resolutions: Vec::new(),
+    };
+    for item in value.split(',').map(str::trim) {
+        if let Some(size) = parse_resolution(item) {
+            filter.resolutions.push(size);
+        } else if item.len() == 4 && item.chars().all(|c| c.is_ascii_alphanumeric()) {
+            filter.pixel_formats.push(item.to_uppercase());
+        } else {
+            return Err(format!(
+                "'{item}' is neither a FourCC such as NV12 nor a WxH size"
+            ));
+        }
+    }
+    Ok(filter)
+}
+
+/// The value of `--device`: a 1-based device number, or text to match against device
+/// names, paths and USB IDs.
+fn device_selector(value: &str) -> Result<DeviceSelector, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("device numbers start at 1".to_string()),
+        Ok(n) => Ok(DeviceSelector::Index(n - 1)),
+        Err(_) => Ok(DeviceSelector::Query(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, clap::Error> {
+        parse_from(std::iter::once("webcam-colorspace").chain(args.iter().copied()))
+    }
+
+    fn capture(args: &[&str]) -> CaptureTestArgs {
+        match parse(args).map(|i| i.command) {
+            Ok(Command::CaptureTest(args)) => *args,
+            Ok(_) => panic!("not a capture: {args:?}"),
+            Err(e) => panic!("{args:?}: {e}"),
+        }
+    }
+
+    fn error(args: &[&str]) -> ErrorKind {
+        match parse(args) {
+            Ok(_) => panic!("accepted: {args:?}"),
+            Err(e) => e.kind(),
+        }
+    }
+
+    #[test]
+    fn no_arguments_lists_every_device() {
+        let invocation = parse(&[]).unwrap();
+        assert!(!invocation.json && !invocation.quiet);
+        assert!(matches!(invocation.command, Command::List { device: None }));
+    }
+
+    #[test]
+    fn json_applies_to_list_and_check_only() {
+        assert!(parse(&["--json"]).unwrap().json);
+        assert!(parse(&["check", "--json"]).unwrap().json);
+        assert_eq!(error(&["--json", "capture"]), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn global_options_go_before_or_after_the_subcommand() {
+        for args in [
+            &["--device", "2", "capture", "-q"][..],
+            &["capture", "-d", "2", "--quiet"],
+        ] {
+            let invocation = parse(args).unwrap();
+            assert!(invocation.quiet);
+            match invocation.command {
+                Command::CaptureTest(c) => assert_eq!(c.device, DeviceSelector::Index(1)),
+                _ => panic!("not a capture"),
+            }
+        }
+    }
+
+    #[test]
+    fn device_numbers_are_one_based() {
+        assert_eq!(capture(&["capture"]).device, DeviceSelector::Index(0));
+        assert_eq!(error(&["-d", "0", "capture"]), ErrorKind::ValueValidation);
+        assert_eq!(
+            capture(&["-d", "046d:0825", "capture"]).device,
+            DeviceSelector::Query("046d:0825".to_string())
+        );
+        match parse(&["-d", "HD Webcam"]).unwrap().command {
+            Command::List { device } => {
+                assert_eq!(device, Some(DeviceSelector::Query("HD Webcam".to_string())))
+            }
+            _ => panic!("not a list"),
+        }
+    }
+
+    #[test]
+    fn resolution_is_case_insensitive_and_checked() {
+        assert_eq!(
+            capture(&["capture", "1280X720"]).resolution,
+            Some((1280, 720))
+        );
+        assert_eq!(capture(&["capture"]).resolution, None);
+        assert_eq!(error(&["capture", "1280x"]), ErrorKind::ValueValidation);
+        assert_eq!(error(&["capture", "720p"]), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn frames_are_bounded() {
+        assert_eq!(capture(&["capture"]).frames, 1);
+        assert_eq!(capture(&["capture", "--frames", "120"]).frames, 120);
+        assert_eq!(
+            error(&["capture", "--frames", "0"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            error(&["capture", "--frames", "121"]),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn settle_policies() {
+        assert!(matches!(
+            capture(&["capture", "--settle", "30"]).settle,
+            SettlePolicy::Fixed(30)
+        ));
+        match capture(&["capture", "--settle-frames", "5", "--settle-timeout", "2.5"]).settle {
+            SettlePolicy::Adaptive {
+                tolerance,
+                stable_frames,
+                timeout,
+            } => {
+                assert_eq!(tolerance, settle::DEFAULT_TOLERANCE);
+                assert_eq!(stable_frames, 5);
+                assert_eq!(timeout, Duration::from_millis(2500));
+            }
+            SettlePolicy::Fixed(_) => panic!("expected adaptive settling"),
+        }
+        assert_eq!(
+            error(&["capture", "--settle", "5", "--settle-tolerance", "2"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["capture", "--settle-tolerance", "0"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            error(&["capture", "--settle", "soon"]),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn controls_keep_a_fixed_order() {
+        let args = capture(&["capture", "--gain", "0", "--exposure", "auto"]);
+        let controls: Vec<CameraControl> = args.controls.iter().map(|s| s.control).collect();
+        assert!(controls == [CameraControl::Exposure, CameraControl::Gain]);
+        assert!(matches!(args.controls[1].value, ControlValue::Manual(0)));
+        let args = capture(&["capture", "--exposure", "-6"]);
+        assert!(matches!(args.controls[0].value, ControlValue::Manual(-6)));
+        assert_eq!(
+            error(&["capture", "--exposure", "fast"]),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn charts() {
+        let args = capture(&["capture", "--colorbars", "auto"]);
+        let chart = args.analysis.chart.unwrap();
+        assert_eq!(chart.layout.name, chart::SMPTE_BARS.name);
+        assert!(chart.corners.is_none());
+
+        let args = capture(&["capture", "--colorchecker", "0,0,10,0,10,10,0,10"]);
+        assert!(args.analysis.chart.unwrap().corners.is_some());
+
+        assert_eq!(
+            error(&["capture", "--colorchecker", "1,2,3"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            error(&["capture", "--colorchecker", "auto", "--colorbars", "auto"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn batch_options() {
+        let args = capture(&["capture", "--all-formats", "--formats", "nv12, 1280x720"]);
+        assert_eq!(
+            args.formats,
+            Some(FormatFilter {
+                pixel_formats: vec!["NV12".to_string()],
+                resolutions: vec![(1280, 720)],
+            })
+        );
+        assert_eq!(
+            error(&["capture", "--all-formats", "--formats", "H.264"]),
+            ErrorKind::ValueValidation
+        );
+        for conflicting in [
+            &["-d", "2", "capture", "--all-devices"][..],
+            &["capture", "--all-formats", "640x480"],
+            &["capture", "--formats", "NV12"],
+            &["capture", "--all-devices", "--mjpg"],
+            &["capture", "--all-formats", "--colorbars", "0,0,1,0,1,1,0,1"],
+        ] {
+            assert_eq!(
+                error(conflicting),
+                ErrorKind::ArgumentConflict,
+                "{conflicting:?}"
+            );
+        }
+        assert!(capture(&["capture", "--all-formats", "--colorbars", "auto"]).all_formats);
+    }
+
+    #[test]
+    fn name_templates_are_checked() {
+        let args = capture(&["capture", "--name", "{device}_{timestamp}"]);
+        assert_eq!(
+            args.analysis.name_template.as_deref(),
+            Some("{device}_{timestamp}")
+        );
+        assert_eq!(
+            error(&["capture", "--name", "{date}"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            error(&["capture", "--name", "{device"]),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn decode_needs_a_size_unless_y4m() {
+        match parse(&[
+            "decode",
+            "frame.nv12",
+            "--size",
+            "640x480",
+            "--range",
+            "full",
+            "--report",
+        ])
+        .unwrap()
+        .command
+        {
+            Command::Decode(args) => {
+                assert_eq!(args.file, PathBuf::from("frame.nv12"));
+                assert_eq!(args.size, Some((640, 480)));
+                assert_eq!(args.full_range, Some(true));
+                assert!(args.analysis.report);
+            }
+            _ => panic!("not a decode"),
+        }
+        assert_eq!(
+            error(&["decode", "frame.nv12"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        match parse(&["decode", "clip.Y4M", "--frame", "3"])
+            .unwrap()
+            .command
+        {
+            Command::Decode(args) => {
+                assert_eq!(args.size, None);
+                assert_eq!(args.full_range, None);
+                assert_eq!(args.frame, Some(2));
+            }
+            _ => panic!("not a decode"),
+        }
+        assert_eq!(
+            error(&["decode", "frame.nv12", "--size", "640x480", "--frame", "2"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["decode", "clip.y4m", "--frame", "0"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            error(&["capture", "--mjpg", "--save-y4m"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn record_options() {
+        match parse(&["record"]).unwrap().command {
+            Command::Record(args) => {
+                assert_eq!(args.duration, Duration::from_secs(10));
+                assert!(matches!(args.settle, SettlePolicy::Adaptive { .. }));
+                assert!(args.controls.is_empty());
+            }
+            _ => panic!("not a record"),
+        }
+        match parse(&[
+            "-d",
+            "2",
+            "record",
+            "640x480",
+            "--duration",
+            "30",
+            "--settle",
+            "0",
+            "--exposure",
+            "-6",
+            "--force",
+        ])
+        .unwrap()
+        .command
+        {
+            Command::Record(args) => {
+                assert_eq!(args.device, DeviceSelector::Index(1));
+                assert_eq!(args.resolution, Some((640, 480)));
+                assert_eq!(args.duration, Duration::from_secs(30));
+                assert!(matches!(args.settle, SettlePolicy::Fixed(0)));
+                assert_eq!(args.controls.len(), 1);
+                assert!(args.force);
+            }
+            _ => panic!("not a record"),
+        }
+        for invalid in ["0", "601", "1.5"] {
+            assert_eq!(
+                error(&["record", "--duration", invalid]),
+                ErrorKind::ValueValidation,
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn snapshot_and_diff() {
+        match parse(&["-d", "2", "snapshot", "cams.json", "--force"])
+            .unwrap()
+            .command
+        {
+            Command::Snapshot {
+                file,
+                force,
+                device,
+            } => {
+                assert_eq!(file, PathBuf::from("cams.json"));
+                assert!(force);
+                assert_eq!(device, Some(DeviceSelector::Index(1)));
+            }
+            _ => panic!("not a snapshot"),
+        }
+        match parse(&["diff", "old.json", "new.json"]).unwrap().command {
+            Command::Diff {
+                baseline, current, ..
+            } => {
+                assert_eq!(baseline, PathBuf::from("old.json"));
+                assert_eq!(current, Some(PathBuf::from("new.json")));
+            }
+            _ => panic!("not a diff"),
+        }
+        assert_eq!(
+            error(&["diff", "old.json", "new.json", "-d", "1"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(error(&["diff"]), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn matrix_commands() {
+        match parse(&["force", "BT709", "-d", "2"]).unwrap().command {
+            Command::ForceMatrix {
+                matrix,
+                device,
+                capture,
+            } => {
+                assert_eq!(matrix, MatrixChoice::Bt709);
+                assert_eq!(device, DeviceSelector::Index(1));
+                assert!(capture.is_none());
+            }
+            _ => panic!("not force"),
+        }
+        match parse(&["force", "bt601", "640x480", "--capture", "--force"])
+            .unwrap()
+            .command
+        {
+            Command::ForceMatrix {
+                capture: Some(capture),
+                ..
+            } => {
+                assert_eq!(capture.resolution, Some((640, 480)));
+                assert!(capture.force);
+            }
+            _ => panic!("not force --capture"),
+        }
+        assert_eq!(error(&["force", "bt2020"]), ErrorKind::InvalidValue);
+        assert_eq!(error(&["force"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            error(&["force", "bt709", "1280x720"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        if cfg!(windows) {
+            assert!(capture(&["capture", "--compare-os", "--all-devices"]).compare_os);
+            assert_eq!(
+                error(&["capture", "--compare-os", "--mjpg"]),
+                ErrorKind::ArgumentConflict
+            );
+        } else {
+            assert_eq!(
+                error(&["capture", "--compare-os"]),
+                ErrorKind::UnknownArgument
+            );
+        }
+
+        match parse(&[
+            "add-quirk",
+            "bt709",
+            "full",
+            "--any-revision",
+            "--note",
+            "ISP",
+        ])
+        .unwrap()
+        .command
+        {
+            Command::AddQuirk(args) => {
+                assert_eq!(args.matrix, MatrixChoice::Bt709);
+                assert_eq!(args.full_range, Some(true));
+                assert!(args.any_revision);
+                assert_eq!(args.note.as_deref(), Some("ISP"));
+            }
+            _ => panic!("not add-quirk"),
+        }
+    }
+
+    #[test]
+    fn old_flag_spellings_still_work() {
+        assert_eq!(
+            capture(&["--capture-test", "1280x720"]).resolution,
+            Some((1280, 720))
+        );
+        assert!(matches!(
+            parse(&["--force-matrix", "bt601"]).unwrap().command,
+            Command::ForceMatrix { .. }
+        ));
+    }
+
+    #[test]
+    fn unknown_arguments_are_rejected() {
+        assert_eq!(error(&["capture", "--bogus"]), ErrorKind::UnknownArgument);
+        assert_eq!(error(&["bogus"]), ErrorKind::InvalidSubcommand);
+    }
+
+    #[test]
+    fn command_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
 }
@@ -0,0 +1,169 @@
+use std::fmt;
+
+/// Camera controls that change the picture between captures and can be pinned.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CameraControl {
+    Exposure,
+    WhiteBalance,
+    Gain,
+    BacklightCompensation,
+}
+
+impl CameraControl {
+    pub const ALL: [CameraControl; 4] = [
+        CameraControl::Exposure,
+        CameraControl::WhiteBalance,
+        CameraControl::Gain,
+        CameraControl::BacklightCompensation,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraControl::Exposure => "Exposure",
+            CameraControl::WhiteBalance => "White balance",
+            CameraControl::Gain => "Gain",
+            CameraControl::BacklightCompensation => "Backlight compensation",
+        }
+    }
+
+    /// Unit suffix for manual values, in the platform's native units.
+    fn unit(self) -> &'static str {
+        match self {
+            CameraControl::WhiteBalance => " K",
+            CameraControl::Exposure if cfg!(windows) => " (log2 s)",
+            CameraControl::Exposure => " (100 µs)",
+            _ => "",
+        }
+    }
+}
+
+/// A control's mode and value as the driver reports it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ControlState {
+    /// The camera adjusts the value itself; always false for controls without an auto mode.
+    pub auto: bool,
+    pub value: i32,
+    /// The driver's own mode value, for auto modes with more than one setting (V4L2's
+    /// exposure menu), so that restoring puts back exactly the mode the camera had.
+    pub mode: Option<i64>,
+}
+
+/// Requested value for a control.
+#[derive(Clone, Copy)]
+pub enum ControlValue {
+    Auto,
+    Manual(i32),
+}
+
+impl ControlValue {
+    /// The state to write, given the control's current one.
+    pub fn apply_to(self, current: ControlState) -> ControlState {
+        match self {
+            ControlValue::Auto => ControlState {
+                auto: true,
+                value: current.value,
+                mode: None,
+            },
+            ControlValue::Manual(value) => ControlState {
+                auto: false,
+                value,
+                mode: None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ControlSetting {
+    pub control: CameraControl,
+    pub value: ControlValue,
+}
+
+/// One line of the control report: a control's state, or why it couldn't be read.
+struct Reading {
+    control: CameraControl,
+    state: Result<ControlState, String>,
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  {:<24}", format!("{}:", self.control.name()))?;
+        match &self.state {
+            Ok(state) => write!(f, "{}", describe(self.control, *state)),
+            Err(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+fn describe(control: CameraControl, state: ControlState) -> String {
+    if state.auto {
+        format!("auto (currently {}{})", state.value, control.unit())
+    } else {
+        format!("{}{}", state.value, control.unit())
+    }
+}
+
+/// Print every control's state, apply `settings`, run `capture`, then put back
+/// whatever was changed — also when applying a setting or the capture fails.
+///
+/// `read` returns `Ok(None)` for controls the device doesn't have.
+pub fn with_controls<T>(
+    settings: &[ControlSetting],
+    read: impl Fn(CameraControl) -> anyhow::Result<Option<ControlState>>,
+    write: impl Fn(CameraControl, ControlState) -> anyhow::Result<()>,
+    capture: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut original = Vec::new();
+    println!("Camera controls:");
+    for control in CameraControl::ALL {
+        let state = match read(control) {
+            Ok(Some(state)) => {
+                original.push((control, state));
+                Ok(state)
+            }
+            Ok(None) => Err("not supported".to_string()),
+            Err(e) => Err(format!("unreadable ({e:#})")),
+        };
+        println!("{}", Reading { control, state });
+    }
+
+    let mut changed: Vec<(CameraControl, ControlState)> = Vec::new();
+    let mut result = Ok(());
+    for setting in settings {
+        let Some(&(_, current)) = original.iter().find(|(c, _)| *c == setting.control) else {
+            result = Err(anyhow::anyhow!(
+                "{} is not available on this device",
+                setting.control.name()
+            ));
+            break;
+        };
+        let wanted = setting.value.apply_to(current);
+        // A write can fail halfway, e.g. exposure switched to manual but the value
+        // refused, so restore the control whether or not it took
+        changed.push((setting.control, current));
+        if let Err(e) = write(setting.control, wanted) {
+            result = Err(e.context(format!("Failed to set {}", setting.control.name())));
+            break;
+        }
+        // Drivers clamp or round to their step; report what the camera actually took
+        let now = read(setting.control).ok().flatten().unwrap_or(wanted);
+        println!(
+            "Set {} to {}",
+            setting.control.name().to_lowercase(),
+            describe(setting.control, now)
+        );
+    }
+
+    let result = result.and_then(|()| capture());
+
+    // Restore in reverse order of application
+    for (control, state) in changed.into_iter().rev() {
+        if let Err(e) = write(control, state) {
+            eprintln!(
+                "Warning: failed to restore {}: {e:#}",
+                control.name().to_lowercase()
+            );
+        }
+    }
+    result
+}
@@ -0,0 +1,595 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::chart::{self, Layout};
+use crate::geometry::{Homography, Point};
+
+/// Detection runs on the luma plane box-filtered down to at most this width.
+const WORK_WIDTH: u32 = 480;
+/// Central-difference luma gradient at or above which a pixel is an edge. Edge pixels
+/// belong to no region, so boundaries can't bridge two neighbouring patches.
+const EDGE_GRADIENT: i32 = 10;
+/// Largest luma step between neighbouring pixels inside one flat region.
+const LOCAL_STEP: i32 = 6;
+/// Largest luma difference from a region's seed pixel, so smooth gradients don't merge.
+const SEED_SPREAD: i32 = 16;
+/// Smallest region, in work pixels, considered as a patch.
+const MIN_AREA: usize = 24;
+/// Area over 12·√det(covariance) for a filled rectangle or ellipse is ~1; holes and
+/// concave shapes score lower.
+const MIN_SOLIDITY: f64 = 0.8;
+const MAX_SOLIDITY: f64 = 1.15;
+/// Elongation (major/minor axis) separating square checker patches from tall bars.
+const BAR_ELONGATION: f64 = 2.0;
+
+/// A chart located in a frame.
+pub struct Detection {
+    /// Maps the layout's unit square onto the frame.
+    pub chart: Homography,
+    /// Patches located directly; any others are interpolated from the fit.
+    pub patches_found: usize,
+}
+
+/// Locate `layout` in an NV12 frame by segmenting the luma plane into flat regions
+/// and finding a set of them arranged like the chart's patch grid.
+pub fn find_chart(
+    layout: &Layout,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    full_range: bool,
+) -> Option<Detection> {
+    let plane = LumaPlane::downsample(data, width, height);
+    let blobs = flat_regions(&plane);
+
+    let (hypotheses, patches_found) = if layout.rows == 1 {
+        bar_correspondences(layout, &blobs)?
+    } else {
+        grid_correspondences(layout, &blobs)?
+    };
+
+    let mut candidates = Vec::new();
+    for mut pairs in hypotheses {
+        // Keep the layout unmirrored: v clockwise from u when seen in the frame
+        let Some(fitted) = Homography::fit(&pairs) else {
+            continue;
+        };
+        let [tl, tr, _, bl] = fitted.corners();
+        if (tr.x - tl.x) * (bl.y - tl.y) - (tr.y - tl.y) * (bl.x - tl.x) < 0.0 {
+            for (uv, _) in &mut pairs {
+                uv.y = 1.0 - uv.y;
+            }
+        }
+        // The grid alone can't tell a chart from one rotated 180°; the patch colours can
+        let rotated = pairs
+            .iter()
+            .map(|&(uv, p)| {
+                let uv = Point {
+                    x: 1.0 - uv.x,
+                    y: 1.0 - uv.y,
+                };
+                (uv, p)
+            })
+            .collect::<Vec<_>>();
+        candidates.push(pairs);
+        candidates.push(rotated);
+    }
+
+    candidates
+        .iter()
+        .filter_map(|pairs| Homography::fit(pairs))
+        .filter_map(|chart| {
+            let samples = chart::sample_patches(layout, data, width, height, &chart);
+            if !chart::patches_outside(layout, &samples).is_empty() {
+                return None;
+            }
+            let error = chart::evaluate(layout, samples, full_range).best().mean;
+            Some((chart, error))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(chart, _)| Detection {
+            chart,
+            patches_found,
+        })
+}
+
+struct LumaPlane {
+    width: usize,
+    height: usize,
+    /// Frame pixels per work pixel along each axis.
+    factor: usize,
+    data: Vec<u8>,
+}
+
+impl LumaPlane {
+    /// Box-filter the Y plane by an integer factor (at least 2, to average out sensor noise).
+    fn downsample(data: &[u8], width: u32, height: u32) -> Self {
+        let (w, h) = (width as usize, height as usize);
+        let factor = (w.div_ceil(WORK_WIDTH as usize)).max(2);
+        let (lw, lh) = (w / factor, h / factor);
+
+        let mut out = vec![0u8; lw * lh];
+        for y in 0..lh {
+            for x in 0..lw {
+                let mut sum = 0u32;
+                for dy in 0..factor {
+                    let row = (y * factor + dy) * w + x * factor;
+                    sum += data[row..row + factor]
+                        .iter()
+                        .map(|&v| v as u32)
+                        .sum::<u32>();
+                }
+                out[y * lw + x] = (sum / (factor * factor) as u32) as u8;
+            }
+        }
+
+        LumaPlane {
+            width: lw,
+            height: lh,
+            factor,
+            data: out,
+        }
+    }
+}
+
+/// A flat, solid region, in frame coordinates.
+struct Blob {
+    area: f64,
+    centre: Point,
+    /// Unit vector along the longer principal axis.
+    major: Point,
+    /// Standard deviations along the major and minor axes.
+    sd_major: f64,
+    sd_minor: f64,
+}
+
+impl Blob {
+    fn elongation(&self) -> f64 {
+        self.sd_major / self.sd_minor
+    }
+
+    /// Full side lengths, assuming a filled rectangle (variance = side² / 12).
+    fn length(&self) -> f64 {
+        self.sd_major * 12f64.sqrt()
+    }
+
+    fn thickness(&self) -> f64 {
+        self.sd_minor * 12f64.sqrt()
+    }
+}
+
+/// Split the plane into 4-connected regions of near-constant luma and keep the
+/// solid, convex ones.
+fn flat_regions(plane: &LumaPlane) -> Vec<Blob> {
+    let (w, h) = (plane.width, plane.height);
+    let max_area = w * h / 8;
+    let mut visited = edge_map(plane);
+    let mut stack = Vec::new();
+    let mut blobs = Vec::new();
+
+    for start in 0..w * h {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let seed = plane.data[start] as i32;
+
+        let (mut n, mut sx, mut sy, mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % w, i / w);
+            let (fx, fy) = (x as f64, y as f64);
+            n += 1.0;
+            sx += fx;
+            sy += fy;
+            sxx += fx * fx;
+            sxy += fx * fy;
+            syy += fy * fy;
+
+            let here = plane.data[i] as i32;
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for j in neighbours.into_iter().flatten() {
+                let v = plane.data[j] as i32;
+                if !visited[j] && (v - here).abs() <= LOCAL_STEP && (v - seed).abs() <= SEED_SPREAD
+                {
+                    visited[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+
+        if (n as usize) < MIN_AREA || n as usize > max_area {
+            continue;
+        }
+        let (mx, my) = (sx / n, sy / n);
+        let (a, b, c) = (sxx / n - mx * mx, sxy / n - mx * my, syy / n - my * my);
+        let spread = (((a - c) / 2.0).powi(2) + b * b).sqrt();
+        let (l1, l2) = ((a + c) / 2.0 + spread, (a + c) / 2.0 - spread);
+        if l2 <= 0.0 {
+            continue;
+        }
+        let solidity = n / (12.0 * (l1 * l2).sqrt());
+        if !(MIN_SOLIDITY..=MAX_SOLIDITY).contains(&solidity) {
+            continue;
+        }
+
+        let theta = 0.5 * (2.0 * b).atan2(a - c);
+        let f = plane.factor as f64;
+        blobs.push(Blob {
+            area: n * f * f,
+            centre: Point {
+                x: (mx + 0.5) * f,
+                y: (my + 0.5) * f,
+            },
+            major: Point {
+                x: theta.cos(),
+                y: theta.sin(),
+            },
+            sd_major: l1.sqrt() * f,
+            sd_minor: l2.sqrt() * f,
+        });
+    }
+    blobs
+}
+
+/// Pixels on an edge (including the plane border) are marked `true`.
+fn edge_map(plane: &LumaPlane) -> Vec<bool> {
+    let (w, h) = (plane.width, plane.height);
+    let at = |x: usize, y: usize| plane.data[y * w + x] as i32;
+    let mut edges = vec![true; w * h];
+    // Too small to have an interior; everything is border
+    if w < 3 || h < 3 {
+        return edges;
+    }
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let dx = (at(x + 1, y) - at(x - 1, y)).abs();
+            let dy = (at(x, y + 1) - at(x, y - 1)).abs();
+            edges[y * w + x] = dx.max(dy) >= EDGE_GRADIENT;
+        }
+    }
+    edges
+}
+
+/// Candidate (unit-square, frame) pairings to choose between by colour, plus how
+/// many patches were located.
+type Correspondences = (Vec<Vec<(Point, Point)>>, usize);
+
+/// Find the largest set of similar squares forming a `cols` × `rows` lattice
+/// (in either orientation) and pair each with its patch centre in the unit square.
+/// Whole edge rows or columns may be missing, e.g. in shadow, so every placement of
+/// the found extent within the chart is a hypothesis for the colours to decide.
+fn grid_correspondences(layout: &Layout, blobs: &[Blob]) -> Option<Correspondences> {
+    let squares: Vec<usize> = (0..blobs.len())
+        .filter(|&i| blobs[i].elongation() < BAR_ELONGATION)
+        .collect();
+    let needed = (layout.patches.len() * 2).div_ceil(3);
+
+    let mut best: Option<HashMap<(i32, i32), usize>> = None;
+    for &seed in &squares {
+        let side = blobs[seed].area.sqrt();
+        let similar: Vec<usize> = squares
+            .iter()
+            .copied()
+            .filter(|&i| (0.5..=2.0).contains(&(blobs[i].area / blobs[seed].area)))
+            .collect();
+
+        let Some(lattice) = grow_lattice(blobs, &similar, seed, side) else {
+            continue;
+        };
+        if lattice.len() > best.as_ref().map_or(needed - 1, |b| b.len()) {
+            best = Some(lattice);
+            if best.as_ref().unwrap().len() == layout.patches.len() {
+                break;
+            }
+        }
+    }
+    let lattice = best?;
+
+    let imin = lattice.keys().map(|k| k.0).min()?;
+    let imax = lattice.keys().map(|k| k.0).max()?;
+    let jmin = lattice.keys().map(|k| k.1).min()?;
+    let jmax = lattice.keys().map(|k| k.1).max()?;
+    let extent = ((imax - imin + 1) as usize, (jmax - jmin + 1) as usize);
+
+    let mut hypotheses = Vec::new();
+    for transposed in [false, true] {
+        // The chart's size along the lattice's i and j axes
+        let (ci, cj) = if transposed {
+            (layout.rows, layout.cols)
+        } else {
+            (layout.cols, layout.rows)
+        };
+        if extent.0 > ci || extent.1 > cj {
+            continue;
+        }
+        for di in 0..=ci - extent.0 {
+            for dj in 0..=cj - extent.1 {
+                let pairs = lattice
+                    .iter()
+                    .map(|(&(i, j), &b)| {
+                        let (i, j) = ((i - imin) as usize + di, (j - jmin) as usize + dj);
+                        let (col, row) = if transposed { (j, i) } else { (i, j) };
+                        let uv = Point {
+                            x: (col as f64 + 0.5) / layout.cols as f64,
+                            y: (row as f64 + 0.5) / layout.rows as f64,
+                        };
+                        (uv, blobs[b].centre)
+                    })
+                    .collect::<Vec<_>>();
+                hypotheses.push(pairs);
+            }
+        }
+    }
+    if hypotheses.is_empty() {
+        return None;
+    }
+    Some((hypotheses, lattice.len()))
+}
+
+/// Walk outwards from `seed` one lattice step at a time, letting the step vectors
+/// follow the observed spacing so perspective doesn't throw the prediction off.
+fn grow_lattice(
+    blobs: &[Blob],
+    candidates: &[usize],
+    seed: usize,
+    side: f64,
+) -> Option<HashMap<(i32, i32), usize>> {
+    let origin = blobs[seed].centre;
+    let offset = |i: usize| sub(blobs[i].centre, origin);
+    let plausible = |d: Point| (0.9 * side..=2.0 * side).contains(&len(d));
+
+    let e1 = candidates
+        .iter()
+        .filter(|&&i| i != seed)
+        .map(|&i| offset(i))
+        .filter(|&d| plausible(d))
+        .min_by(|a, b| len(*a).total_cmp(&len(*b)))?;
+    let e2 = candidates
+        .iter()
+        .filter(|&&i| i != seed)
+        .map(|&i| offset(i))
+        .filter(|&d| plausible(d) && (dot(d, e1) / (len(d) * len(e1))).abs() < 0.5)
+        .min_by(|a, b| len(*a).total_cmp(&len(*b)))?;
+
+    let mut at: HashMap<(i32, i32), usize> = HashMap::new();
+    let mut placed = vec![false; blobs.len()];
+    at.insert((0, 0), seed);
+    placed[seed] = true;
+    let mut queue = VecDeque::from([(seed, 0i32, 0i32, e1, e2)]);
+
+    while let Some((b, i, j, e1, e2)) = queue.pop_front() {
+        let steps = [
+            (1, 0, e1),
+            (-1, 0, scale(e1, -1.0)),
+            (0, 1, e2),
+            (0, -1, scale(e2, -1.0)),
+        ];
+        for (di, dj, step) in steps {
+            let key = (i + di, j + dj);
+            if at.contains_key(&key) {
+                continue;
+            }
+            let predicted = add(blobs[b].centre, step);
+            let Some(next) = nearest(blobs, candidates, predicted, 0.3 * len(step)) else {
+                continue;
+            };
+            if placed[next] {
+                continue;
+            }
+            at.insert(key, next);
+            placed[next] = true;
+
+            let observed = sub(blobs[next].centre, blobs[b].centre);
+            let (ne1, ne2) = if di != 0 {
+                (scale(observed, di as f64), e2)
+            } else {
+                (e1, scale(observed, dj as f64))
+            };
+            queue.push_back((next, key.0, key.1, ne1, ne2));
+        }
+    }
+    Some(at)
+}
+
+/// Find a run of adjacent, parallel bars of similar size and pair the two ends of
+/// each bar's centre line with the top and bottom edges of the unit square. One bar
+/// may be missing from either end (blue often merges into a dark surround).
+fn bar_correspondences(layout: &Layout, blobs: &[Blob]) -> Option<Correspondences> {
+    let bars: Vec<usize> = (0..blobs.len())
+        .filter(|&i| blobs[i].elongation() >= BAR_ELONGATION)
+        .collect();
+
+    for &seed in &bars {
+        let axis = blobs[seed].major;
+        let across = Point {
+            x: -axis.y,
+            y: axis.x,
+        };
+        let similar: Vec<usize> = bars
+            .iter()
+            .copied()
+            .filter(|&i| {
+                (0.5..=2.0).contains(&(blobs[i].area / blobs[seed].area))
+                    && dot(blobs[i].major, axis).abs() > 12f64.to_radians().cos()
+            })
+            .collect();
+
+        // Collect neighbours on either side of the seed, then order them across the bars
+        let mut run = vec![seed];
+        for direction in [1.0, -1.0] {
+            let mut current = seed;
+            while let Some(next) = adjacent_bar(blobs, &similar, current, across, direction) {
+                if run.contains(&next) {
+                    break;
+                }
+                run.push(next);
+                current = next;
+            }
+        }
+        let offsets = if run.len() == layout.cols {
+            vec![0]
+        } else if run.len() + 1 == layout.cols {
+            vec![0, 1]
+        } else {
+            continue;
+        };
+        run.sort_by(|&a, &b| dot(blobs[a].centre, across).total_cmp(&dot(blobs[b].centre, across)));
+
+        let hypotheses = offsets
+            .into_iter()
+            .map(|offset| {
+                let mut pairs = Vec::with_capacity(run.len() * 2);
+                for (k, &b) in run.iter().enumerate() {
+                    let blob = &blobs[b];
+                    let sign = dot(blob.major, axis).signum();
+                    let half = scale(blob.major, sign * blob.length() / 2.0);
+                    let u = ((k + offset) as f64 + 0.5) / layout.cols as f64;
+                    pairs.push((Point { x: u, y: 0.0 }, sub(blob.centre, half)));
+                    pairs.push((Point { x: u, y: 1.0 }, add(blob.centre, half)));
+                }
+                pairs
+            })
+            .collect();
+        return Some((hypotheses, run.len()));
+    }
+    None
+}
+
+/// The bar directly beside `current` in `direction` (±1 along `across`), if any.
+fn adjacent_bar(
+    blobs: &[Blob],
+    candidates: &[usize],
+    current: usize,
+    across: Point,
+    direction: f64,
+) -> Option<usize> {
+    let here = &blobs[current];
+    let along = Point {
+        x: across.y,
+        y: -across.x,
+    };
+    candidates
+        .iter()
+        .copied()
+        .filter(|&i| i != current)
+        .filter_map(|i| {
+            let d = sub(blobs[i].centre, here.centre);
+            let expected = (here.thickness() + blobs[i].thickness()) / 2.0;
+            let step = dot(d, across) * direction;
+            let ok = (0.7 * expected..=1.4 * expected).contains(&step)
+                && dot(d, along).abs() < 0.25 * here.length();
+            ok.then_some((i, (step - expected).abs()))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+fn nearest(blobs: &[Blob], candidates: &[usize], target: Point, radius: f64) -> Option<usize> {
+    candidates
+        .iter()
+        .map(|&i| (i, len(sub(blobs[i].centre, target))))
+        .filter(|&(_, d)| d <= radius)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    Point {
+        x: a.x + b.x,
+        y: a.y + b.y,
+    }
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    Point {
+        x: a.x - b.x,
+        y: a.y - b.y,
+    }
+}
+
+fn scale(a: Point, s: f64) -> Point {
+    Point {
+        x: a.x * s,
+        y: a.y * s,
+    }
+}
+
+fn dot(a: Point, b: Point) -> f64 {
+    a.x * b.x + a.y * b.y
+}
+
+fn len(a: Point) -> f64 {
+    a.x.hypot(a.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chart::COLORCHECKER;
+    use crate::yuv::BT601;
+
+    /// Limited-range BT.601 Y'CbCr of an sRGB colour.
+    fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+        let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+        let kg = 1.0 - BT601.kr - BT601.kb;
+        let y = BT601.kr * r + kg * g + BT601.kb * b;
+        let cb = (b - y) / (2.0 * (1.0 - BT601.kb));
+        let cr = (r - y) / (2.0 * (1.0 - BT601.kr));
+        [16.0 + 219.0 * y, 128.0 + 224.0 * cb, 128.0 + 224.0 * cr].map(|v| v.round() as u8)
+    }
+
+    /// A ColorChecker filling most of an NV12 frame, patches 60 px apart on a dark
+    /// surround, with the columns in `hidden` painted over with the surround.
+    fn chart_frame(hidden: &[usize]) -> (Vec<u8>, u32, u32) {
+        let (width, height) = (480u32, 320u32);
+        let (w, h) = (width as usize, height as usize);
+        let mut data = vec![0; w * h * 3 / 2];
+        for y in 0..h {
+            for x in 0..w {
+                let (col, row) = (
+                    (x as i64 - 60).div_euclid(60),
+                    (y as i64 - 40).div_euclid(60),
+                );
+                let inside =
+                    (x as i64 - 60).rem_euclid(60) >= 8 && (y as i64 - 40).rem_euclid(60) >= 8;
+                let patch = (0..6).contains(&col) && (0..4).contains(&row) && inside;
+                let yuv = if patch && !hidden.contains(&(col as usize)) {
+                    ycbcr(COLORCHECKER.patches[row as usize * 6 + col as usize].srgb)
+                } else {
+                    [30, 128, 128]
+                };
+                data[y * w + x] = yuv[0];
+                let uv = w * h + (y / 2) * w + (x / 2) * 2;
+                data[uv] = yuv[1];
+                data[uv + 1] = yuv[2];
+            }
+        }
+        (data, width, height)
+    }
+
+    #[test]
+    fn finds_a_chart_with_an_edge_column_missing() {
+        for hidden in [[0], [5]] {
+            let (data, width, height) = chart_frame(&hidden);
+            let found = find_chart(&COLORCHECKER, &data, width, height, false)
+                .unwrap_or_else(|| panic!("no chart with column {} hidden", hidden[0] + 1));
+            assert_eq!(found.patches_found, 20);
+            // Outer corners of the patch grid, with half a gap of surround on each side
+            let expected = [(64.0, 44.0), (424.0, 44.0), (424.0, 284.0), (64.0, 284.0)];
+            for (corner, (x, y)) in found.chart.corners().iter().zip(expected) {
+                assert!(
+                    (corner.x - x).abs() < 6.0 && (corner.y - y).abs() < 6.0,
+                    "column {} hidden: corner at {:.1},{:.1}, expected {x},{y}",
+                    hidden[0] + 1,
+                    corner.x,
+                    corner.y
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/// A point in frame pixel coordinates.
+#[derive(Clone, Copy)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Projective mapping from the unit square (u, v) to frame coordinates.
+pub struct Homography {
+    m: [f64; 9],
+}
+
+impl Homography {
+    /// Map the unit square onto a quadrilateral given as corners in order
+    /// (0,0), (1,0), (1,1), (0,1) — i.e. top-left, top-right, bottom-right, bottom-left.
+    pub fn from_unit_square(quad: [Point; 4]) -> Self {
+        let [p0, p1, p2, p3] = quad;
+        let dx1 = p1.x - p2.x;
+        let dx2 = p3.x - p2.x;
+        let dx3 = p0.x - p1.x + p2.x - p3.x;
+        let dy1 = p1.y - p2.y;
+        let dy2 = p3.y - p2.y;
+        let dy3 = p0.y - p1.y + p2.y - p3.y;
+
+        let (g, h) = if dx3.abs() < 1e-12 && dy3.abs() < 1e-12 {
+            // Parallelogram: affine
+            (0.0, 0.0)
+        } else {
+            let det = dx1 * dy2 - dx2 * dy1;
+            ((dx3 * dy2 - dx2 * dy3) / det, (dx1 * dy3 - dx3 * dy1) / det)
+        };
+
+        Homography {
+            m: [
+                p1.x - p0.x + g * p1.x,
+                p3.x - p0.x + h * p3.x,
+                p0.x,
+                p1.y - p0.y + g * p1.y,
+                p3.y - p0.y + h * p3.y,
+                p0.y,
+                g,
+                h,
+                1.0,
+            ],
+        }
+    }
+
+    /// Least-squares fit from (unit-square, frame) point pairs; needs at least four
+    /// pairs, not all collinear.
+    pub fn fit(pairs: &[(Point, Point)]) -> Option<Self> {
+        if pairs.len() < 4 {
+            return None;
+        }
+
+        // Normalize frame coordinates so the normal equations stay well conditioned
+        let n = pairs.len() as f64;
+        let mx = pairs.iter().map(|(_, p)| p.x).sum::<f64>() / n;
+        let my = pairs.iter().map(|(_, p)| p.y).sum::<f64>() / n;
+        let scale = pairs
+            .iter()
+            .map(|(_, p)| (p.x - mx).hypot(p.y - my))
+            .sum::<f64>()
+            / n;
+        if scale < 1e-9 {
+            return None;
+        }
+
+        // Unknowns a..h of x = (au + bv + c) / (gu + hv + 1), y = (du + ev + f) / (gu + hv + 1)
+        let mut ata = [[0.0f64; 8]; 8];
+        let mut atb = [0.0f64; 8];
+        for (uv, p) in pairs {
+            let (u, v) = (uv.x, uv.y);
+            let x = (p.x - mx) / scale;
+            let y = (p.y - my) / scale;
+            let rows = [
+                ([u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x], x),
+                ([0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y], y),
+            ];
+            for (row, rhs) in rows {
+                for i in 0..8 {
+                    for j in 0..8 {
+                        ata[i][j] += row[i] * row[j];
+                    }
+                    atb[i] += row[i] * rhs;
+                }
+            }
+        }
+        let h = solve8(ata, atb)?;
+
+        // Undo the normalization: x = scale * x' + mx
+        Some(Homography {
+            m: [
+                scale * h[0] + mx * h[6],
+                scale * h[1] + mx * h[7],
+                scale * h[2] + mx,
+                scale * h[3] + my * h[6],
+                scale * h[4] + my * h[7],
+                scale * h[5] + my,
+                h[6],
+                h[7],
+                1.0,
+            ],
+        })
+    }
+
+    /// Frame positions of the unit square's corners (TL, TR, BR, BL).
+    pub fn corners(&self) -> [Point; 4] {
+        [
+            self.map(0.0, 0.0),
+            self.map(1.0, 0.0),
+            self.map(1.0, 1.0),
+            self.map(0.0, 1.0),
+        ]
+    }
+
+    pub fn map(&self, u: f64, v: f64) -> Point {
+        let m = &self.m;
+        let w = m[6] * u + m[7] * v + m[8];
+        Point {
+            x: (m[0] * u + m[1] * v + m[2]) / w,
+            y: (m[3] * u + m[4] * v + m[5]) / w,
+        }
+    }
+}
+
+/// Gaussian elimination with partial pivoting; `None` if the system is singular.
+fn solve8(mut a: [[f64; 8]; 8], mut b: [f64; 8]) -> Option<[f64; 8]> {
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1..8 {
+            let f = a[row][col] / pivot_row[col];
+            for (x, p) in a[row].iter_mut().zip(pivot_row).skip(col) {
+                *x -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = [0.0; 8];
+    for row in (0..8).rev() {
+        let sum: f64 = (row + 1..8).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
@@ -0,0 +1,165 @@
+use crate::canvas::{self, Canvas};
+use crate::yuv::{self, ClipStats, YuvMatrix};
+
+/// One decode of the frame under a particular matrix + range assumption.
+pub struct Variant {
+    pub matrix: &'static YuvMatrix,
+    pub full_range: bool,
+    pub image: Canvas,
+    pub clip: ClipStats,
+}
+
+impl Variant {
+    pub fn label(&self) -> String {
+        format!(
+            "{} {}",
+            self.matrix.name,
+            if self.full_range { "full" } else { "limited" }
+        )
+    }
+}
+
+/// Thumbnail width of each contact sheet cell.
+const CELL_WIDTH: u32 = 480;
+const MARGIN: u32 = 12;
+const LABEL_SCALE: u32 = 2;
+
+/// Decode an NV12 frame under every matrix × range combination.
+/// Order is row-major: limited range first, then full range.
+pub fn decode_variants(data: &[u8], width: u32, height: u32) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    for full_range in [false, true] {
+        for matrix in yuv::CANDIDATE_MATRICES {
+            let (rgb, clip) =
+                yuv::nv12_to_rgb24_with_stats(data, width, height, matrix, full_range);
+            variants.push(Variant {
+                matrix,
+                full_range,
+                image: Canvas::from_rgb(width, height, rgb),
+                clip,
+            });
+        }
+    }
+    variants
+}
+
+/// Every variant with its mean absolute difference per sample from `rgb`, an RGB24
+/// image of the same frame converted elsewhere (e.g. by the OS), closest first.
+pub fn rank_variants<'a>(variants: &'a [Variant], rgb: &[u8]) -> Vec<(&'a Variant, f64)> {
+    let mut ranked: Vec<(&Variant, f64)> = variants
+        .iter()
+        .map(|v| {
+            let n = v.image.data.len().min(rgb.len()).max(1);
+            let sum: u64 = v
+                .image
+                .data
+                .iter()
+                .zip(rgb)
+                .map(|(&a, &b)| a.abs_diff(b) as u64)
+                .sum();
+            (v, sum as f64 / n as f64)
+        })
+        .collect();
+    ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+    ranked
+}
+
+/// Lay the variants out as a labelled grid: one column per matrix, one row per range.
+pub fn contact_sheet(variants: &[Variant], reported: &str) -> Canvas {
+    let (width, height) = (variants[0].image.width, variants[0].image.height);
+    let cols = yuv::CANDIDATE_MATRICES.len() as u32;
+    let rows = (variants.len() as u32).div_ceil(cols);
+
+    let thumb_w = CELL_WIDTH.min(width);
+    let thumb_h = (height as u64 * thumb_w as u64 / width as u64).max(1) as u32;
+    let label_h = canvas::GLYPH_HEIGHT * LABEL_SCALE + 8;
+    let header_h = label_h + MARGIN;
+
+    let sheet_w = cols * (thumb_w + MARGIN) + MARGIN;
+    let sheet_h = header_h + rows * (label_h + thumb_h + MARGIN) + MARGIN;
+
+    let mut sheet = Canvas::new(sheet_w, sheet_h, [32, 32, 32]);
+    sheet.text(
+        MARGIN as i64,
+        MARGIN as i64,
+        &format!("Decode grid  -  driver reports: {reported}"),
+        LABEL_SCALE,
+        [255, 255, 255],
+    );
+
+    for (i, variant) in variants.iter().enumerate() {
+        let col = i as u32 % cols;
+        let row = i as u32 / cols;
+        let x = (MARGIN + col * (thumb_w + MARGIN)) as i64;
+        let y = (header_h + MARGIN + row * (label_h + thumb_h + MARGIN)) as i64;
+
+        let label = format!(
+            "{}  clip {:.1}%/{:.1}%",
+            variant.label(),
+            variant.clip.low_percent(),
+            variant.clip.high_percent()
+        );
+        let label = truncate_to_width(&label, thumb_w);
+        sheet.text(x, y, &label, LABEL_SCALE, [230, 230, 120]);
+
+        sheet.blit_scaled(&variant.image, x, y + label_h as i64, thumb_w, thumb_h);
+    }
+
+    sheet
+}
+
+/// RGB distance at which the difference heatmap saturates to white.
+const HEATMAP_FULL_SCALE: f64 = 64.0;
+
+/// Per-pixel RGB distance between two decodes of the same frame, black (identical)
+/// through red and yellow to white, with a scale bar underneath. The scale is fixed
+/// so heatmaps of different captures can be compared.
+pub fn difference_heatmap(a: &Canvas, b: &Canvas) -> Canvas {
+    let (width, height) = (a.width, a.height);
+    let scale = (width / 320).max(1);
+    let legend_h = canvas::GLYPH_HEIGHT * scale * 2 + 16;
+    let mut map = Canvas::new(width, height + legend_h, [32, 32, 32]);
+
+    for (i, (pa, pb)) in a
+        .data
+        .chunks_exact(3)
+        .zip(b.data.chunks_exact(3))
+        .enumerate()
+    {
+        let distance = pa
+            .iter()
+            .zip(pb)
+            .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        map.data[i * 3..i * 3 + 3].copy_from_slice(&heat_color(distance / HEATMAP_FULL_SCALE));
+    }
+
+    let bar_w = width / 2;
+    let bar_y = (height + 4) as i64;
+    for x in 0..bar_w {
+        let color = heat_color(x as f64 / bar_w as f64);
+        map.fill_rect(x as i64 + 4, bar_y, 1, canvas::GLYPH_HEIGHT * scale, color);
+    }
+    let text_y = bar_y + (canvas::GLYPH_HEIGHT * scale) as i64 + 4;
+    map.text(4, text_y, "0", scale, [230, 230, 230]);
+    let max_label = format!("{HEATMAP_FULL_SCALE:.0}+ RGB codes");
+    let max_x = (bar_w + 4).saturating_sub(canvas::text_width(&max_label, scale));
+    map.text(max_x as i64, text_y, &max_label, scale, [230, 230, 230]);
+    map
+}
+
+/// Black -> red -> yellow -> white over `t` in 0..1.
+fn heat_color(t: f64) -> canvas::Rgb {
+    let t = t.clamp(0.0, 1.0) * 3.0;
+    let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0) as u8;
+    [channel(t), channel(t - 1.0), channel(t - 2.0)]
+}
+
+fn truncate_to_width(text: &str, max_width: u32) -> String {
+    let mut out = text.to_string();
+    while !out.is_empty() && canvas::text_width(&out, LABEL_SCALE) > max_width {
+        out.pop();
+    }
+    out
+}
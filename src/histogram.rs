@@ -0,0 +1,138 @@
+/// Per-plane sample histograms of an NV12 frame.
+pub struct PlaneHistograms {
+    pub y: [u64; 256],
+    pub cb: [u64; 256],
+    pub cr: [u64; 256],
+}
+
+/// Sample counts outside the limited-range bounds (Y 16–235, Cb/Cr 16–240).
+pub struct RangeStats {
+    pub y_total: u64,
+    pub y_below: u64,
+    pub y_above: u64,
+    pub c_total: u64,
+    pub c_below: u64,
+    pub c_above: u64,
+    pub y_min: u8,
+    pub y_max: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RangeInference {
+    Full,
+    Limited,
+    /// The scene doesn't exercise enough of the range to tell.
+    Inconclusive,
+}
+
+impl RangeInference {
+    pub fn label(self) -> &'static str {
+        match self {
+            RangeInference::Full => "Full (0-255)",
+            RangeInference::Limited => "Limited (16-235)",
+            RangeInference::Inconclusive => "Inconclusive",
+        }
+    }
+}
+
+/// Fraction of samples outside the limited range above which the stream must be full range.
+const OUTSIDE_THRESHOLD: f64 = 0.001;
+
+/// Fraction of luma samples piled up exactly on 16 or 235 that indicates limited-range clipping.
+const CLIP_PILE_THRESHOLD: f64 = 0.001;
+
+/// Histograms of an NV12 frame; all empty if `data` is too short for the frame size.
+pub fn nv12_histograms(data: &[u8], width: u32, height: u32) -> PlaneHistograms {
+    let w = width as usize;
+    let h = height as usize;
+    let mut hist = PlaneHistograms {
+        y: [0; 256],
+        cb: [0; 256],
+        cr: [0; 256],
+    };
+    if data.len() < w * h + w * (h / 2) {
+        return hist;
+    }
+    let y_plane = &data[..w * h];
+    let uv_plane = &data[w * h..w * h + w * (h / 2)];
+
+    for &y in y_plane {
+        hist.y[y as usize] += 1;
+    }
+    for uv in uv_plane.chunks_exact(2) {
+        hist.cb[uv[0] as usize] += 1;
+        hist.cr[uv[1] as usize] += 1;
+    }
+
+    hist
+}
+
+impl PlaneHistograms {
+    pub fn range_stats(&self) -> RangeStats {
+        let sum = |h: &[u64; 256], r: std::ops::Range<usize>| -> u64 { h[r].iter().sum() };
+
+        let y_total = sum(&self.y, 0..256);
+        let c_total = sum(&self.cb, 0..256) + sum(&self.cr, 0..256);
+
+        RangeStats {
+            y_total,
+            y_below: sum(&self.y, 0..16),
+            y_above: sum(&self.y, 236..256),
+            c_total,
+            c_below: sum(&self.cb, 0..16) + sum(&self.cr, 0..16),
+            c_above: sum(&self.cb, 241..256) + sum(&self.cr, 241..256),
+            y_min: self.y.iter().position(|&n| n > 0).unwrap_or(0) as u8,
+            y_max: self.y.iter().rposition(|&n| n > 0).unwrap_or(0) as u8,
+        }
+    }
+
+    /// Guess the actual signal range from the sample distribution.
+    ///
+    /// Samples outside 16–235/16–240 can only occur in a full-range stream. A
+    /// limited-range stream that hits its bounds piles up on exactly 16 or 235 with
+    /// nothing beyond them. Anything else (e.g. a dim, low-contrast scene, or a few
+    /// stray samples outside the bounds) can't be decided.
+    pub fn infer_range(&self) -> RangeInference {
+        let stats = self.range_stats();
+        if stats.y_total == 0 {
+            return RangeInference::Inconclusive;
+        }
+
+        if stats.y_outside_fraction() > OUTSIDE_THRESHOLD
+            || stats.c_outside_fraction() > OUTSIDE_THRESHOLD
+        {
+            return RangeInference::Full;
+        }
+
+        let inside = stats.y_below + stats.y_above + stats.c_below + stats.c_above == 0;
+        if !inside {
+            return RangeInference::Inconclusive;
+        }
+        let total = stats.y_total as f64;
+        let piled_low = self.y[16] as f64 / total > CLIP_PILE_THRESHOLD;
+        let piled_high = self.y[235] as f64 / total > CLIP_PILE_THRESHOLD;
+        if piled_low || piled_high {
+            return RangeInference::Limited;
+        }
+
+        RangeInference::Inconclusive
+    }
+}
+
+impl RangeStats {
+    pub fn y_outside_fraction(&self) -> f64 {
+        fraction(self.y_below + self.y_above, self.y_total)
+    }
+
+    pub fn c_outside_fraction(&self) -> f64 {
+        fraction(self.c_below + self.c_above, self.c_total)
+    }
+}
+
+fn fraction(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
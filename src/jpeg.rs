@@ -0,0 +1,261 @@
+/// Colour signalling found in the marker segments of a JPEG bitstream.
+///
+/// MJPG cameras carry their real colour description inside each frame, not in
+/// the driver's media type. Decoders follow the JFIF convention (BT.601 matrix,
+/// full range) unless an Adobe APP14 marker says otherwise.
+#[derive(Default)]
+pub struct JpegColorInfo {
+    pub jfif: Option<JfifHeader>,
+    pub adobe: Option<AdobeHeader>,
+    pub exif_colorspace: Option<u16>,
+    pub icc: Option<IccProfileInfo>,
+    /// Component identifiers from the SOF marker, in order.
+    pub components: Vec<u8>,
+}
+
+pub struct JfifHeader {
+    pub version: (u8, u8),
+}
+
+pub struct AdobeHeader {
+    pub version: u16,
+    /// 0 = no transform (RGB/CMYK), 1 = YCbCr, 2 = YCCK.
+    pub transform: u8,
+}
+
+pub struct IccProfileInfo {
+    pub size: usize,
+    pub version: (u8, u8),
+    /// Data colour space signature, e.g. "RGB " or "YCbr".
+    pub color_space: String,
+    pub description: Option<String>,
+}
+
+/// How a standard decoder will interpret the samples in the frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JpegEncoding {
+    /// YCbCr with BT.601 coefficients over the full 0-255 range (JFIF).
+    YCbCrBt601Full,
+    /// Components are stored as RGB; no YUV matrix is involved.
+    Rgb,
+    Grayscale,
+    Unknown,
+}
+
+impl JpegEncoding {
+    pub fn description(self) -> &'static str {
+        match self {
+            JpegEncoding::YCbCrBt601Full => "YCbCr, BT.601 matrix, full range (JFIF)",
+            JpegEncoding::Rgb => "RGB (no YUV matrix)",
+            JpegEncoding::Grayscale => "Grayscale",
+            JpegEncoding::Unknown => "Unknown",
+        }
+    }
+}
+
+impl JpegColorInfo {
+    /// Work out the encoding a decoder will assume, following the same rules
+    /// as libjpeg: JFIF wins, then the Adobe transform flag, then component IDs.
+    pub fn implied_encoding(&self) -> JpegEncoding {
+        match self.components.len() {
+            1 => return JpegEncoding::Grayscale,
+            3 => {}
+            _ => return JpegEncoding::Unknown,
+        }
+
+        if self.jfif.is_some() {
+            return JpegEncoding::YCbCrBt601Full;
+        }
+
+        if let Some(adobe) = &self.adobe {
+            return match adobe.transform {
+                0 => JpegEncoding::Rgb,
+                _ => JpegEncoding::YCbCrBt601Full,
+            };
+        }
+
+        if self.components == [b'R', b'G', b'B'] {
+            JpegEncoding::Rgb
+        } else {
+            JpegEncoding::YCbCrBt601Full
+        }
+    }
+}
+
+/// Walk the marker segments of a JPEG frame up to the start of scan.
+pub fn parse_markers(data: &[u8]) -> anyhow::Result<JpegColorInfo> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        anyhow::bail!("Not a JPEG frame (missing SOI marker)");
+    }
+
+    let mut info = JpegColorInfo::default();
+    let mut icc_chunks: Vec<(u8, &[u8])> = Vec::new();
+    let mut pos = 2;
+
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            anyhow::bail!("Corrupt JPEG: expected marker at offset {pos}");
+        }
+        let marker = data[pos + 1];
+
+        // Fill bytes and standalone markers carry no length field
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        // SOS: entropy-coded data follows, no more headers of interest
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if len < 2 || pos + 2 + len > data.len() {
+            anyhow::bail!("Corrupt JPEG: segment 0xFF{marker:02X} at offset {pos} overruns frame");
+        }
+        let payload = &data[pos + 4..pos + 2 + len];
+
+        match marker {
+            0xE0 if payload.starts_with(b"JFIF\0") && payload.len() >= 7 => {
+                info.jfif = Some(JfifHeader {
+                    version: (payload[5], payload[6]),
+                });
+            }
+            0xE1 if payload.starts_with(b"Exif\0\0") => {
+                info.exif_colorspace = parse_exif_colorspace(&payload[6..]);
+            }
+            0xE2 if payload.starts_with(b"ICC_PROFILE\0") && payload.len() >= 14 => {
+                icc_chunks.push((payload[12], &payload[14..]));
+            }
+            0xEE if payload.starts_with(b"Adobe") && payload.len() >= 12 => {
+                info.adobe = Some(AdobeHeader {
+                    version: u16::from_be_bytes([payload[5], payload[6]]),
+                    transform: payload[11],
+                });
+            }
+            // SOF0..SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+            0xC0..=0xCF
+                if marker != 0xC4 && marker != 0xC8 && marker != 0xCC && payload.len() >= 6 =>
+            {
+                let count = payload[5] as usize;
+                info.components = payload[6..].chunks(3).take(count).map(|c| c[0]).collect();
+            }
+            _ => {}
+        }
+
+        pos += 2 + len;
+    }
+
+    if !icc_chunks.is_empty() {
+        icc_chunks.sort_by_key(|(seq, _)| *seq);
+        let profile: Vec<u8> = icc_chunks
+            .iter()
+            .flat_map(|(_, c)| c.iter().copied())
+            .collect();
+        info.icc = parse_icc_header(&profile);
+    }
+
+    Ok(info)
+}
+
+/// Find the ColorSpace tag (0xA001) in the Exif sub-IFD.
+fn parse_exif_colorspace(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |off: usize| -> Option<u16> {
+        let b = tiff.get(off..off + 2)?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let read_u32 = |off: usize| -> Option<u32> {
+        let b = tiff.get(off..off + 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    };
+    // Returns the value offset of `tag` within the IFD at `ifd`
+    let find_tag = |ifd: usize, tag: u16| -> Option<usize> {
+        let count = read_u16(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| read_u16(entry) == Some(tag))
+            .map(|entry| entry + 8)
+    };
+
+    let ifd0 = read_u32(4)? as usize;
+    let exif_ifd = read_u32(find_tag(ifd0, 0x8769)?)? as usize;
+    read_u16(find_tag(exif_ifd, 0xA001)?)
+}
+
+fn parse_icc_header(profile: &[u8]) -> Option<IccProfileInfo> {
+    if profile.len() < 132 {
+        return None;
+    }
+    let color_space = String::from_utf8_lossy(&profile[16..20]).to_string();
+    let version = (profile[8], profile[9] >> 4);
+
+    // Tag table follows the 128-byte header
+    let tag_count = u32::from_be_bytes(profile[128..132].try_into().ok()?) as usize;
+    let description = (0..tag_count)
+        .filter_map(|i| profile.get(132 + i * 12..144 + i * 12))
+        .find(|entry| &entry[..4] == b"desc")
+        .and_then(|entry| {
+            let off = u32::from_be_bytes(entry[4..8].try_into().ok()?) as usize;
+            let len = u32::from_be_bytes(entry[8..12].try_into().ok()?) as usize;
+            parse_icc_text(profile.get(off..off + len)?)
+        });
+
+    Some(IccProfileInfo {
+        size: profile.len(),
+        version,
+        color_space,
+        description,
+    })
+}
+
+/// Decode an ICC 'desc' (v2) or 'mluc' (v4) text element, first record only.
+fn parse_icc_text(tag: &[u8]) -> Option<String> {
+    match tag.get(..4)? {
+        b"desc" => {
+            let count = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+            let ascii = tag.get(12..12 + count)?;
+            Some(
+                String::from_utf8_lossy(ascii)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        b"mluc" => {
+            let len = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+            let off = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+            let utf16: Vec<u16> = tag
+                .get(off..off + len)?
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&utf16))
+        }
+        _ => None,
+    }
+}
+
+pub fn exif_colorspace_name(v: u16) -> String {
+    match v {
+        1 => "sRGB".to_string(),
+        2 => "Adobe RGB".to_string(),
+        0xFFFF => "Uncalibrated".to_string(),
+        other => format!("Unknown ({other})"),
+    }
+}
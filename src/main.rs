@@ -1,35 +1,125 @@
 mod bmp;
+mod canvas;
+mod chart;
 mod cli;
+mod color;
+mod controls;
+mod detect;
+mod geometry;
+mod grid;
+mod histogram;
+mod jpeg;
+mod output;
 mod platform;
+mod png;
+mod quirks;
+mod report;
+mod scope;
+mod settle;
+mod snapshot;
+mod temporal;
+mod uvc;
+mod y4m;
 mod yuv;
 
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
 use cli::Command;
-use platform::FormatInfo;
+use output::OutputFiles;
+use platform::{
+    CaptureRequest, CapturedFrame, ColorspaceInfo, ControlInfo, DeviceInfo, FormatInfo,
+};
+
+/// Set by `--quiet`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for banners, progress and guidance, which `--quiet` leaves out.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
 
 fn main() -> anyhow::Result<()> {
-    let command = cli::parse_args()?;
+    let invocation = cli::parse_args();
+    QUIET.store(invocation.quiet, Ordering::Relaxed);
 
-    match command {
-        Command::Enumerate => run_enumerate(),
-        Command::CaptureTest {
-            device_index,
-            resolution,
-            mirror,
-            save_raw,
-        } => run_capture_test(device_index, resolution, mirror, save_raw),
+    match invocation.command {
+        Command::List { device } if invocation.json => run_enumerate_json(device.as_ref()),
+        Command::List { device } => run_enumerate(device.as_ref()),
+        Command::Check { device } => {
+            let problems = run_check(device.as_ref(), invocation.json)?;
+            if problems > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Snapshot {
+            file,
+            force,
+            device,
+        } => run_snapshot(&file, force, device.as_ref()),
+        Command::Diff {
+            baseline,
+            current,
+            device,
+        } => {
+            let changes = run_diff(&baseline, current.as_deref(), device.as_ref())?;
+            if changes > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::CaptureTest(args) => {
+            if args.mjpg {
+                run_mjpg_test(&args)
+            } else {
+                run_capture_test(&args)
+            }
+        }
+        Command::Decode(args) => run_decode(&args),
+        Command::Record(args) => {
+            let changes = run_record(&args)?;
+            if changes > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
         Command::ForceMatrix {
             matrix,
-            device_index,
-        } => run_force_matrix(device_index, matrix),
+            device,
+            capture,
+        } => run_force_matrix(&device, matrix, capture.as_ref()),
+        Command::AddQuirk(args) => run_add_quirk(&args),
+        Command::Completions(shell) => {
+            cli::print_completions(shell);
+            Ok(())
+        }
+    }
+}
+
+/// Every device, or only the one `--device` selects.
+fn selected_devices(device: Option<&cli::DeviceSelector>) -> anyhow::Result<Vec<DeviceInfo>> {
+    let mut devices = platform::enumerate_devices()?;
+    if let Some(selector) = device {
+        let (_, id) = platform::select_device(selector)?;
+        devices.retain(|d| d.path == id.path);
     }
+    Ok(devices)
 }
 
-fn run_enumerate() -> anyhow::Result<()> {
+fn run_enumerate(device: Option<&cli::DeviceSelector>) -> anyhow::Result<()> {
     print_header();
     print_os_info();
     println!();
 
-    let devices = platform::enumerate_devices()?;
+    let devices = selected_devices(device)?;
 
     if devices.is_empty() {
         println!("No camera devices found.");
@@ -43,46 +133,372 @@ fn run_enumerate() -> anyhow::Result<()> {
         if !device.path.is_empty() {
             println!("    Path: {}", device.path);
         }
+        if let Some(usb) = &device.usb {
+            println!("    USB: {}", usb);
+        }
+        if let Some(quirk) = &device.quirk {
+            println!("    Known quirk: {}", quirk.summary());
+        }
 
         if device.formats.is_empty() {
             println!("    No formats reported.");
-            continue;
+        } else {
+            print_formats(&device.formats);
         }
 
-        // Deduplicate: group by pixel_format + resolution, show colorspace once
-        let mut seen = std::collections::HashSet::new();
-        let mut unique_formats: Vec<&FormatInfo> = Vec::new();
+        if !device.uvc_formats.is_empty() {
+            print_uvc_formats(&device.uvc_formats);
+        }
 
-        for fmt in &device.formats {
-            let key = format!("{}|{}|{}", fmt.pixel_format, fmt.resolution, fmt.frame_rate);
-            if seen.insert(key) {
-                unique_formats.push(fmt);
+        if !device.controls.is_empty() {
+            println!("    Controls ({}):", device.controls.len());
+            for control in &device.controls {
+                println!("      {}", format_control(control));
             }
         }
+        println!();
+    }
 
-        println!("    Formats ({} unique):", unique_formats.len());
+    print_legend();
 
-        for fmt in &unique_formats {
-            println!(
-                "      {} {} @ {}",
-                fmt.pixel_format, fmt.resolution, fmt.frame_rate
-            );
+    Ok(())
+}
+
+/// What `check` found wrong with one device.
+#[derive(serde::Serialize)]
+struct CheckResult {
+    device: String,
+    path: String,
+    problems: Vec<String>,
+}
+
+/// Report devices whose driver contradicts their UVC descriptors or a known quirk.
+/// Returns the number of problems found.
+fn run_check(device: Option<&cli::DeviceSelector>, json: bool) -> anyhow::Result<usize> {
+    let results: Vec<CheckResult> = selected_devices(device)?
+        .iter()
+        .map(|d| CheckResult {
+            device: d.name.clone(),
+            path: d.path.clone(),
+            problems: device_problems(d),
+        })
+        .collect();
+    let problems = results.iter().map(|r| r.problems.len()).sum();
 
-            let cs = &fmt.colorspace;
-            let matrix_display = format_matrix_highlight(&cs.matrix);
-            println!("        Primaries: {}", cs.primaries);
-            println!("        YUV Matrix: {}", matrix_display);
-            println!("        Transfer: {}", cs.transfer);
-            println!("        Range: {}", cs.range);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(problems);
+    }
+    if results.is_empty() {
+        println!("No camera devices found.");
+    }
+    for (i, result) in results.iter().enumerate() {
+        if result.problems.is_empty() {
+            println!("Device {}: {}: OK", i + 1, result.device);
+        } else {
+            println!("Device {}: {}:", i + 1, result.device);
+            for problem in &result.problems {
+                println!("  ! {problem}");
+            }
         }
-        println!();
     }
+    if problems > 0 {
+        info!();
+        info!("Confirm with a capture test before relying on either side; see README.md.");
+    }
+    Ok(problems)
+}
 
-    print_legend();
+/// Contradictions between what the driver reports and what the descriptors or the
+/// quirks database say.
+fn device_problems(device: &DeviceInfo) -> Vec<String> {
+    let mut problems: Vec<String> = device
+        .uvc_formats
+        .iter()
+        .filter(|f| f.mismatch)
+        .map(|f| {
+            format!(
+                "{} (format {}): driver reports {} but the UVC descriptor says {}",
+                f.pixel_format,
+                f.index,
+                f.reported_matrix.as_deref().unwrap_or("nothing"),
+                f.matrix
+            )
+        })
+        .collect();
 
+    if let Some(quirk) = &device.quirk {
+        let advertised = advertised_matrix(&device.formats);
+        if let (Some(actual), Some(advertised)) = (&quirk.actual_matrix, &advertised) {
+            if actual != advertised {
+                problems.push(format!(
+                    "known quirk ({}): driver reports {advertised} but the firmware encodes {actual}",
+                    quirk.device
+                ));
+            }
+        }
+        let reported_range = ["NV12", "YUYV", "YUY2"]
+            .iter()
+            .find_map(|name| device.formats.iter().find(|f| f.pixel_format == *name))
+            .map(|f| f.colorspace.range.to_lowercase());
+        if let (Some(actual), Some(reported)) = (&quirk.actual_range, &reported_range) {
+            if (reported.starts_with("full") || reported.starts_with("limited"))
+                && !reported.starts_with(actual.as_str())
+            {
+                problems.push(format!(
+                    "known quirk ({}): driver reports {reported} range but the firmware uses {actual}",
+                    quirk.device
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn print_formats(formats: &[FormatInfo]) {
+    // Deduplicate: group by pixel_format + resolution, show colorspace once
+    let mut seen = std::collections::HashSet::new();
+    let mut unique_formats: Vec<&FormatInfo> = Vec::new();
+
+    for fmt in formats {
+        let key = format!(
+            "{}|{}|{}",
+            fmt.pixel_format, fmt.resolution, fmt.frame_rates
+        );
+        if seen.insert(key) {
+            unique_formats.push(fmt);
+        }
+    }
+
+    println!("    Formats ({} unique):", unique_formats.len());
+
+    for fmt in &unique_formats {
+        println!(
+            "      {} {} @ {}",
+            fmt.pixel_format, fmt.resolution, fmt.frame_rates
+        );
+        if let Some(range) = &fmt.size_range {
+            println!("        Sizes: {range} (standard sizes probed)");
+        }
+
+        let cs = &fmt.colorspace;
+        let matrix_display = format_matrix_highlight(&cs.matrix);
+        println!("        Primaries: {}", cs.primaries);
+        println!("        YUV Matrix: {}", matrix_display);
+        println!("        Transfer: {}", cs.transfer);
+        println!("        Range: {}", cs.range);
+    }
+}
+
+/// The descriptor formats with their Color Matching data and the driver's matrix.
+fn print_uvc_formats(formats: &[platform::UvcFormat]) {
+    println!("    UVC descriptors:");
+    for fmt in formats {
+        println!(
+            "      {} (format {}, interface {}): {}",
+            fmt.pixel_format,
+            fmt.index,
+            fmt.interface,
+            fmt.frame_sizes.join(", ")
+        );
+        println!(
+            "        {}: primaries {}, transfer {}, matrix {}",
+            if fmt.color_matching {
+                "Color Matching"
+            } else {
+                "No Color Matching descriptor, UVC defaults"
+            },
+            fmt.primaries,
+            fmt.transfer,
+            fmt.matrix
+        );
+        match &fmt.reported_matrix {
+            Some(reported) if fmt.mismatch => println!(
+                "        Driver reports: {reported} <-- driver translation error, descriptor says {}",
+                fmt.matrix
+            ),
+            Some(reported) => println!("        Driver reports: {reported}"),
+            None => println!("        Driver reports: format not listed"),
+        }
+    }
+}
+
+/// One line per control: "Name: current (kind range, default; flags)".
+fn format_control(control: &ControlInfo) -> String {
+    let menu_name = |v: i64| {
+        control
+            .menu
+            .iter()
+            .find(|entry| entry.value == v)
+            .map(|entry| entry.name.clone())
+    };
+    let value_text = |v: i64| match control.kind.as_str() {
+        "boolean" => (if v != 0 { "on" } else { "off" }).to_string(),
+        "menu" | "integer menu" => menu_name(v).unwrap_or_else(|| v.to_string()),
+        _ => v.to_string(),
+    };
+
+    let current = control
+        .current
+        .map(value_text)
+        .unwrap_or_else(|| "-".to_string());
+    let details = match control.kind.as_str() {
+        "boolean" => format!("boolean, default {}", value_text(control.default)),
+        "menu" | "integer menu" => {
+            let entries: Vec<String> = control
+                .menu
+                .iter()
+                .map(|entry| format!("{} {}", entry.value, entry.name))
+                .collect();
+            format!(
+                "{}: {}; default {}",
+                control.kind,
+                entries.join(", "),
+                value_text(control.default)
+            )
+        }
+        "integer" | "integer64" | "bitmask" => format!(
+            "{} {}..{}, step {}, default {}",
+            control.kind, control.minimum, control.maximum, control.step, control.default
+        ),
+        other => other.to_string(),
+    };
+    let flags = if control.flags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", control.flags.join(", "))
+    };
+    format!("{}: {} ({}){}", control.name, current, details, flags)
+}
+
+/// The enumeration as one JSON document on stdout, for scripts and bug reports.
+fn run_enumerate_json(device: Option<&cli::DeviceSelector>) -> anyhow::Result<()> {
+    let report = snapshot::Snapshot {
+        os: os_description(),
+        devices: selected_devices(device)?,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+/// Save the enumeration for a later `diff`.
+fn run_snapshot(
+    file: &std::path::Path,
+    force: bool,
+    device: Option<&cli::DeviceSelector>,
+) -> anyhow::Result<()> {
+    if !force && file.exists() {
+        anyhow::bail!(
+            "{} already exists. Use --force to overwrite it.",
+            file.display()
+        );
+    }
+    let snapshot = snapshot::Snapshot {
+        os: os_description(),
+        devices: selected_devices(device)?,
+    };
+    snapshot.save(file)?;
+    println!(
+        "Saved {} device(s) to {}",
+        snapshot.devices.len(),
+        file.display()
+    );
+    Ok(())
+}
+
+/// Compare a snapshot with the live enumeration, or with `current`.
+/// Returns the number of changes found.
+fn run_diff(
+    baseline: &std::path::Path,
+    current: Option<&std::path::Path>,
+    device: Option<&cli::DeviceSelector>,
+) -> anyhow::Result<usize> {
+    let before = snapshot::Snapshot::load(baseline)?;
+    let after = match current {
+        Some(path) => snapshot::Snapshot::load(path)?,
+        None => snapshot::Snapshot {
+            os: os_description(),
+            devices: selected_devices(device)?,
+        },
+    };
+    let after_name = current.map_or("live enumeration".to_string(), |p| p.display().to_string());
+    info!("Comparing {} with {}", baseline.display(), after_name);
+    if before.os != after.os {
+        info!("OS: {} -> {}", before.os, after.os);
+    }
+    info!();
+
+    let mut diffs = snapshot::diff(&before.devices, &after.devices);
+    if device.is_some() {
+        // Only the selected camera is enumerated, so the others aren't gone
+        diffs.retain(|d| !matches!(d.kind, snapshot::DiffKind::Removed));
+    }
+    if diffs.is_empty() {
+        println!("No changes.");
+        return Ok(0);
+    }
+    for diff in &diffs {
+        match &diff.kind {
+            snapshot::DiffKind::Added => println!("{}: added", diff.device),
+            snapshot::DiffKind::Removed => println!("{}: removed", diff.device),
+            snapshot::DiffKind::Changed(changes) => {
+                println!("{}:", diff.device);
+                for change in changes {
+                    println!("  {change}");
+                }
+            }
+        }
+    }
+    Ok(diffs.iter().map(|d| d.kind.count()).sum())
+}
+
+fn run_add_quirk(args: &cli::AddQuirkArgs) -> anyhow::Result<()> {
+    let (_, id) = platform::select_device(&args.device)?;
+    let devices = platform::enumerate_devices()?;
+    let device = devices
+        .iter()
+        .find(|d| d.path == id.path)
+        .ok_or_else(|| anyhow::anyhow!("Failed to read {}", id))?;
+    let usb = device.usb.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} has no USB vendor and product ID to record a quirk against.",
+            device.name
+        )
+    })?;
+
+    if !args.any_revision && usb.bcd_device.is_none() {
+        eprintln!(
+            "Warning: {}'s firmware revision (bcdDevice) is unknown, so this quirk will cover \
+             every revision of {}:{}. Pass --any-revision to say that is intended.",
+            device.name, usb.vendor_id, usb.product_id
+        );
+    }
+    let quirk = quirks::Quirk {
+        device: quirks::device_key(usb, !args.any_revision),
+        name: Some(device.name.clone()),
+        advertised_matrix: advertised_matrix(&device.formats),
+        actual_matrix: Some(args.matrix.name().to_string()),
+        actual_range: args
+            .full_range
+            .map(|full| if full { "full" } else { "limited" }.to_string()),
+        note: args.note.clone(),
+    };
+    println!("Quirk for {} ({}):", device.name, quirk.device);
+    println!("  {}", quirk.summary());
+    let path = quirks::add(quirk)?;
+    println!("Saved to {}", path.display());
     Ok(())
 }
 
+/// The matrix reported for the raw format a capture test would use, if the driver states one.
+fn advertised_matrix(formats: &[FormatInfo]) -> Option<String> {
+    ["NV12", "YUYV", "YUY2"]
+        .iter()
+        .find_map(|name| formats.iter().find(|f| f.pixel_format == *name))
+        .map(|f| f.colorspace.matrix.clone())
+        .filter(|m| !["Not specified", "Unknown", "Default"].contains(&m.as_str()))
+}
+
 fn mirror_rgb(data: &mut [u8], width: u32, height: u32) {
     let w = width as usize;
     let row_bytes = w * 3;
@@ -100,21 +516,60 @@ fn mirror_rgb(data: &mut [u8], width: u32, height: u32) {
     }
 }
 
-fn run_capture_test(
-    device_index: usize,
-    resolution: Option<(u32, u32)>,
-    mirror: bool,
-    save_raw: bool,
-) -> anyhow::Result<()> {
+fn run_capture_test(args: &cli::CaptureTestArgs) -> anyhow::Result<()> {
     print_header();
-    println!();
 
-    let frame = platform::capture_frame(device_index, resolution)?;
+    if args.all_devices || args.all_formats {
+        return run_capture_batch(args);
+    }
+    let (device_index, id) = platform::select_device(&args.device)?;
+    let dir = args.analysis.output_dir.clone().unwrap_or_default();
+    capture_and_decode(args, device_index, &id, args.resolution, &|_| dir.clone())?;
+    Ok(())
+}
+
+/// What one capture test found, for the `--all-devices`/`--all-formats` summary.
+struct CaptureOutcome {
+    dir: PathBuf,
+    advertised_matrix: String,
+    full_range: bool,
+    inferred_range: histogram::RangeInference,
+    /// The matrix a chart measurement picked, if a chart was measured.
+    detected_matrix: Option<&'static str>,
+}
+
+/// Scope images, in the order `write_scopes` renders them.
+const SCOPE_OUTPUTS: [&str; 4] = [
+    "scope_waveform",
+    "scope_parade_bt601",
+    "scope_parade_bt709",
+    "scope_vectorscope",
+];
+
+/// Capture from one device and write the decodes and any requested analysis into the
+/// directory `out_dir` names for the frame (empty for the current one), named by the
+/// `--name` template.
+fn capture_and_decode(
+    args: &cli::CaptureTestArgs,
+    device_index: usize,
+    id: &platform::DeviceId,
+    resolution: Option<(u32, u32)>,
+    out_dir: &dyn Fn(&CapturedFrame) -> PathBuf,
+) -> anyhow::Result<CaptureOutcome> {
+    let mut frame = platform::capture_frame(&CaptureRequest {
+        device_index,
+        resolution,
+        pixel_format: "NV12",
+        frames: args.frames,
+        settle: args.settle,
+        controls: &args.controls,
+    })?;
 
     println!(
         "Captured {} frame: {}x{}",
         frame.pixel_format, frame.width, frame.height
     );
+    print_known_quirk(&frame);
 
     if frame.pixel_format != "NV12" {
         anyhow::bail!(
@@ -134,8 +589,52 @@ fn run_capture_test(
         );
     }
 
-    if save_raw {
-        let raw_path = std::path::PathBuf::from("capture_raw.nv12");
+    // Straight after the raw capture, so both see the same scene
+    let os_rgb = if args.compare_os {
+        Some(platform::capture_os_rgb(&CaptureRequest {
+            device_index,
+            resolution: Some((frame.width, frame.height)),
+            pixel_format: "NV12",
+            frames: 1,
+            settle: args.settle,
+            controls: &args.controls,
+        })?)
+    } else {
+        None
+    };
+
+    let dir = out_dir(&frame);
+    let files = output_files(&args.analysis, dir.clone(), &id.name, &frame);
+    let mut planned = planned_outputs(&args.analysis);
+    if args.save_raw {
+        planned.push(("capture_raw", "nv12"));
+    }
+    if args.save_y4m {
+        planned.push(("capture_raw", "y4m"));
+    }
+    if args.compare_os {
+        planned.push(("capture_os", "bmp"));
+    }
+    files.prepare(&planned)?;
+
+    if args.save_y4m {
+        let path = files.path("capture_raw", "y4m");
+        let mut writer =
+            y4m::Y4mWriter::create(&path, frame.width, frame.height, None, frame.full_range)?;
+        for data in frame.earlier_frames.iter().chain([&frame.data]) {
+            writer.write_frame(data)?;
+        }
+        let count = writer.finish()?;
+        println!("Saved Y4M: {} ({} frame(s))", path.display(), count);
+        info!("  play: mpv {}", path.display());
+    }
+
+    if !frame.earlier_frames.is_empty() {
+        average_frames(&mut frame, expected_size)?;
+    }
+
+    if args.save_raw {
+        let raw_path = files.path("capture_raw", "nv12");
         std::fs::write(&raw_path, &frame.data)?;
         println!(
             "Saved raw NV12: {} ({} bytes)",
@@ -148,9 +647,60 @@ fn run_capture_test(
         );
     }
 
+    let analysis = analyse_frame(&args.analysis, &frame, &files, id, Some(device_index))?;
+    if let Some(os_rgb) = os_rgb {
+        compare_os_conversion(&frame, os_rgb, args.analysis.mirror, &files)?;
+    }
+
+    Ok(CaptureOutcome {
+        dir,
+        advertised_matrix: frame.colorspace.matrix,
+        full_range: frame.full_range,
+        inferred_range: analysis.inferred_range,
+        detected_matrix: analysis.chart.map(|c| c.report.best().matrix.name),
+    })
+}
+
+/// The files `analyse_frame` writes for these options.
+fn planned_outputs(analysis: &cli::AnalysisArgs) -> Vec<(&'static str, &'static str)> {
+    let mut planned = vec![("capture_bt601", "bmp"), ("capture_bt709", "bmp")];
+    if analysis.grid {
+        planned.push(("capture_grid", "bmp"));
+    }
+    if analysis.scopes {
+        planned.extend(SCOPE_OUTPUTS.map(|name| (name, "bmp")));
+    }
+    if analysis.chart.is_some() {
+        planned.push(("capture_chart", "bmp"));
+    }
+    if analysis.report {
+        planned.push(("report", "html"));
+    }
+    planned
+}
+
+/// What `analyse_frame` found.
+struct FrameAnalysis {
+    inferred_range: histogram::RangeInference,
+    /// The chart measurement, if one was requested.
+    chart: Option<ChartVerdict>,
+}
+
+/// Range analysis, the BT.601/BT.709 decodes and whatever else the options ask for.
+/// `id` names where the frame came from and `device_index` is the camera, if it came
+/// from one.
+fn analyse_frame(
+    args: &cli::AnalysisArgs,
+    frame: &CapturedFrame,
+    files: &OutputFiles,
+    id: &platform::DeviceId,
+    device_index: Option<usize>,
+) -> anyhow::Result<FrameAnalysis> {
+    let inferred_range = print_range_analysis(frame);
+
     // Decode with both matrices
     let matrices = [&yuv::BT601, &yuv::BT709];
-    println!("Decoding with {}...", matrices[0].name);
+    info!("Decoding with {}...", matrices[0].name);
     let mut rgb_601 = yuv::nv12_to_rgb24(
         &frame.data,
         frame.width,
@@ -159,7 +709,7 @@ fn run_capture_test(
         frame.full_range,
     );
 
-    println!("Decoding with {}...", matrices[1].name);
+    info!("Decoding with {}...", matrices[1].name);
     let mut rgb_709 = yuv::nv12_to_rgb24(
         &frame.data,
         frame.width,
@@ -168,14 +718,14 @@ fn run_capture_test(
         frame.full_range,
     );
 
-    if mirror {
+    if args.mirror {
         mirror_rgb(&mut rgb_601, frame.width, frame.height);
         mirror_rgb(&mut rgb_709, frame.width, frame.height);
     }
 
     // Write BMP files
-    let path_601 = std::path::PathBuf::from("capture_bt601.bmp");
-    let path_709 = std::path::PathBuf::from("capture_bt709.bmp");
+    let path_601 = files.path("capture_bt601", "bmp");
+    let path_709 = files.path("capture_bt709", "bmp");
 
     bmp::write_bmp(&path_601, frame.width, frame.height, &rgb_601)?;
     println!("Saved: {}", path_601.display());
@@ -183,49 +733,1561 @@ fn run_capture_test(
     bmp::write_bmp(&path_709, frame.width, frame.height, &rgb_709)?;
     println!("Saved: {}", path_709.display());
 
+    info!();
+    info!("Compare the two images side by side:");
+    info!("  - The image with correct colors reveals which matrix the firmware uses.");
+    info!(
+        "  - If {} looks correct, firmware encodes BT.601.",
+        path_601.display()
+    );
+    info!(
+        "  - If {} looks correct, firmware encodes BT.709.",
+        path_709.display()
+    );
+
+    if args.grid {
+        run_decode_grid(frame, args.mirror, files)?;
+    }
+
+    if args.scopes {
+        write_scopes(frame, files)?;
+    }
+
+    let chart = match &args.chart {
+        Some(chart) => Some(run_chart(frame, chart, device_index, files)?),
+        None => None,
+    };
+
+    let analysis = FrameAnalysis {
+        inferred_range,
+        chart,
+    };
+    if args.report {
+        write_report(args, frame, files, id, device_index.is_some(), &analysis)?;
+    }
+    Ok(analysis)
+}
+
+/// Analyse a raw NV12 frame saved by an earlier capture, as if it had just been captured.
+fn run_decode(args: &cli::DecodeArgs) -> anyhow::Result<()> {
+    print_header();
+
+    let mut file = File::open(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let mut data = Vec::new();
+    (&mut file)
+        .take(10)
+        .read_to_end(&mut data)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let (width, height, full_range, data, earlier_frames) = if y4m::is_y4m(&data) {
+        let (clip, mut frames) = load_y4m(args, file)?;
+        let newest = frames.pop().expect("a Y4M file has at least one frame");
+        (
+            clip.width,
+            clip.height,
+            clip.full_range.unwrap_or(false),
+            newest,
+            frames,
+        )
+    } else {
+        let Some((width, height)) = args.size else {
+            anyhow::bail!(
+                "{} is not a Y4M file, so it needs --size.",
+                args.file.display()
+            );
+        };
+        file.read_to_end(&mut data)
+            .with_context(|| format!("Failed to read {}", args.file.display()))?;
+        let full_range = args.full_range.unwrap_or(false);
+        (
+            width,
+            height,
+            full_range,
+            load_nv12(args, data, width, height)?,
+            Vec::new(),
+        )
+    };
+
+    let range = if full_range {
+        "Full (0-255)"
+    } else {
+        "Limited (16-235)"
+    };
+    let mut frame = CapturedFrame {
+        usb: None,
+        width,
+        height,
+        pixel_format: "NV12".to_string(),
+        full_range,
+        colorspace: ColorspaceInfo {
+            primaries: "Unknown".to_string(),
+            matrix: "Not specified".to_string(),
+            transfer: "Unknown".to_string(),
+            range: range.to_string(),
+        },
+        data,
+        earlier_frames,
+    };
+    println!(
+        "Decoding {}: NV12 {}x{}",
+        args.file.display(),
+        width,
+        height
+    );
+    if !frame.earlier_frames.is_empty() {
+        let frame_size = yuv::nv12_len(width, height)
+            .with_context(|| format!("{width}x{height} is not a usable NV12 frame size"))?;
+        average_frames(&mut frame, frame_size)?;
+    }
+
+    let id = platform::DeviceId {
+        name: args
+            .file
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: args.file.display().to_string(),
+        usb: None,
+    };
+    let dir = args.analysis.output_dir.clone().unwrap_or_default();
+    let files = output_files(&args.analysis, dir, &id.name, &frame);
+    files.prepare(&planned_outputs(&args.analysis))?;
+    analyse_frame(&args.analysis, &frame, &files, &id, None)?;
+    Ok(())
+}
+
+/// A raw NV12 frame of the given size; bytes past the first frame are ignored.
+fn load_nv12(
+    args: &cli::DecodeArgs,
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+) -> anyhow::Result<Vec<u8>> {
+    let Some(expected_size) = yuv::nv12_len(width, height) else {
+        anyhow::bail!(
+            "{width}x{height} is not a usable NV12 frame size: width and height must be even \
+             and fit in memory. Check --size."
+        );
+    };
+    if data.len() < expected_size {
+        anyhow::bail!(
+            "{} has {} bytes, but an NV12 {}x{} frame needs {}. Check --size.",
+            args.file.display(),
+            data.len(),
+            width,
+            height,
+            expected_size
+        );
+    }
+    if data.len() > expected_size {
+        println!(
+            "Note: {} has {} bytes more than an NV12 {}x{} frame; they are ignored.",
+            args.file.display(),
+            data.len() - expected_size,
+            width,
+            height
+        );
+    }
+    Ok(data)
+}
+
+/// A Y4M file and the frames to decode from it: the `--frame` picked, or all of them to be
+/// averaged like a `--frames` capture. `full_range` is the range to decode with.
+fn load_y4m(args: &cli::DecodeArgs, file: File) -> anyhow::Result<(y4m::Y4mReader, Vec<Vec<u8>>)> {
+    let mut clip = y4m::Y4mReader::open(file)
+        .with_context(|| format!("{} is not a usable Y4M file", args.file.display()))?;
+    if args
+        .size
+        .is_some_and(|size| size != (clip.width, clip.height))
+    {
+        anyhow::bail!(
+            "{} is {}x{}; leave out --size for Y4M files.",
+            args.file.display(),
+            clip.width,
+            clip.height
+        );
+    }
+    println!(
+        "{}: Y4M {}x{}, {} frame(s){}{}",
+        args.file.display(),
+        clip.width,
+        clip.height,
+        clip.frames,
+        clip.frame_rate
+            .map(|(num, den)| format!(" at {:.2} fps", num as f64 / den as f64))
+            .unwrap_or_default(),
+        match clip.full_range {
+            Some(true) => ", full range",
+            Some(false) => ", limited range",
+            None => "",
+        }
+    );
+    clip.full_range = match (args.full_range, clip.full_range) {
+        (Some(given), Some(file)) if given != file => {
+            println!("Note: --range overrides the file's XCOLORRANGE.");
+            Some(given)
+        }
+        (given, file) => Some(given.or(file).unwrap_or(false)),
+    };
+
+    let indices = match args.frame {
+        Some(index) if index >= clip.frames => anyhow::bail!(
+            "{} has {} frame(s); there is no frame {}.",
+            args.file.display(),
+            clip.frames,
+            index + 1
+        ),
+        Some(index) => index..index + 1,
+        None if clip.frames as u64 > cli::MAX_FRAMES => anyhow::bail!(
+            "{} has {} frames; up to {} can be averaged. Pick one with --frame N.",
+            args.file.display(),
+            clip.frames,
+            cli::MAX_FRAMES
+        ),
+        None => 0..clip.frames,
+    };
+    let frames = indices
+        .map(|index| clip.frame(index))
+        .collect::<anyhow::Result<_>>()
+        .with_context(|| format!("{} is not a usable Y4M file", args.file.display()))?;
+    Ok((clip, frames))
+}
+
+/// How the outputs of a capture are named, from the capture options and the frame.
+fn output_files(
+    args: &cli::AnalysisArgs,
+    dir: PathBuf,
+    device_name: &str,
+    frame: &CapturedFrame,
+) -> OutputFiles {
+    OutputFiles::new(
+        dir,
+        args.name_template
+            .as_deref()
+            .unwrap_or(output::DEFAULT_TEMPLATE),
+        args.force,
+        device_name,
+        (frame.width, frame.height),
+        &frame.colorspace.matrix,
+        frame.full_range,
+    )
+}
+
+/// Raw formats the capture test can decode: NV12, and YUYV, which the Linux backend repacks.
+const RAW_CAPTURE_FORMATS: [&str; 2] = ["NV12", "YUYV"];
+
+/// One row of the batch summary.
+struct BatchEntry {
+    device: String,
+    /// Format and size, or "-" when the device failed before a format was chosen.
+    format: String,
+    outcome: anyhow::Result<CaptureOutcome>,
+}
+
+/// A capture test per device (`--all-devices`) and per raw resolution (`--all-formats`),
+/// each writing into `<device>/<format>_<WxH>/`, followed by a summary table.
+fn run_capture_batch(args: &cli::CaptureTestArgs) -> anyhow::Result<()> {
+    let devices: Vec<(usize, platform::DeviceId)> = if args.all_devices {
+        platform::list_devices()?.into_iter().enumerate().collect()
+    } else {
+        vec![platform::select_device(&args.device)?]
+    };
+    if devices.is_empty() {
+        anyhow::bail!("No camera devices found.");
+    }
+    let root = args.analysis.output_dir.clone().unwrap_or_default();
+    let summary_path = root.join("summary.txt");
+    output::prepare_path(&summary_path, args.analysis.force)?;
+    let infos = if args.all_formats {
+        platform::enumerate_devices()?
+    } else {
+        Vec::new()
+    };
+
+    let mut entries = Vec::new();
+    for (index, id) in &devices {
+        let device = format!("{} {}", index + 1, id.name);
+        let device_dir = root.join(format!(
+            "{}_{}",
+            index + 1,
+            output::file_name_part(&id.name)
+        ));
+
+        // (source format, size) to capture; `None` leaves the choice to the capture
+        let targets = if args.all_formats {
+            let formats = infos
+                .iter()
+                .find(|d| d.path == id.path)
+                .map(|d| raw_capture_targets(&d.formats, args.formats.as_ref()))
+                .unwrap_or_default();
+            if formats.is_empty() {
+                entries.push(BatchEntry {
+                    device,
+                    format: "-".to_string(),
+                    outcome: Err(anyhow::anyhow!("no matching raw YUV formats")),
+                });
+                continue;
+            }
+            formats
+                .into_iter()
+                .map(|(format, size)| (Some(format), Some(size)))
+                .collect::<Vec<_>>()
+        } else {
+            vec![(None, args.resolution)]
+        };
+
+        for (format, resolution) in targets {
+            let label = match (format, resolution) {
+                (Some(format), Some((w, h))) => format!("{format} {w}x{h}"),
+                (None, Some((w, h))) => format!("{w}x{h}"),
+                _ => "highest resolution".to_string(),
+            };
+            println!("━━━ Device {}: {} ━━━", device, label);
+            let outcome = capture_and_decode(args, *index, id, resolution, &|frame| {
+                device_dir.join(format!(
+                    "{}_{}x{}",
+                    format.unwrap_or(&frame.pixel_format),
+                    frame.width,
+                    frame.height
+                ))
+            });
+            if let Err(e) = &outcome {
+                eprintln!("Error: {e:#}");
+            }
+            println!();
+            entries.push(BatchEntry {
+                device: device.clone(),
+                format: label,
+                outcome,
+            });
+        }
+    }
+
+    let summary = batch_summary(&entries, args.analysis.chart.is_some());
+    print!("{summary}");
+    std::fs::write(&summary_path, &summary)?;
+    println!();
+    println!("Saved: {}", summary_path.display());
+    Ok(())
+}
+
+/// Every size offered in a decodable raw format, largest first, each under the format
+/// the capture will pick for it (NV12 before YUYV).
+fn raw_capture_targets<'a>(
+    formats: &'a [FormatInfo],
+    filter: Option<&cli::FormatFilter>,
+) -> Vec<(&'a str, (u32, u32))> {
+    let mut targets: Vec<(&str, (u32, u32))> = Vec::new();
+    for name in RAW_CAPTURE_FORMATS {
+        for format in formats.iter().filter(|f| f.pixel_format == name) {
+            let Some(size) = cli::parse_resolution(&format.resolution) else {
+                continue;
+            };
+            if filter.is_some_and(|f| !f.matches(name, size))
+                || targets.iter().any(|&(_, s)| s == size)
+            {
+                continue;
+            }
+            targets.push((&format.pixel_format, size));
+        }
+    }
+    targets.sort_by_key(|&(_, (w, h))| std::cmp::Reverse(w as u64 * h as u64));
+    targets
+}
+
+/// The advertised vs detected matrix and range of every capture, as a text table.
+fn batch_summary(entries: &[BatchEntry], chart: bool) -> String {
+    let short_range = |full: bool| if full { "Full" } else { "Limited" };
+    let mut rows = vec![[
+        "Device".to_string(),
+        "Format".to_string(),
+        "Advertised".to_string(),
+        "Detected".to_string(),
+        "Range (decoded / inferred)".to_string(),
+        "Output".to_string(),
+    ]];
+    for entry in entries {
+        let row = match &entry.outcome {
+            Ok(o) => [
+                entry.device.clone(),
+                entry.format.clone(),
+                o.advertised_matrix.clone(),
+                o.detected_matrix.unwrap_or("-").to_string(),
+                format!(
+                    "{} / {}",
+                    short_range(o.full_range),
+                    match o.inferred_range {
+                        histogram::RangeInference::Full => "Full",
+                        histogram::RangeInference::Limited => "Limited",
+                        histogram::RangeInference::Inconclusive => "?",
+                    }
+                ),
+                o.dir.display().to_string(),
+            ],
+            Err(e) => [
+                entry.device.clone(),
+                entry.format.clone(),
+                String::new(),
+                String::new(),
+                String::new(),
+                format!("FAILED: {e:#}"),
+            ],
+        };
+        rows.push(row);
+    }
+
+    let mut widths = [0usize; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = format!("Summary ({} captures):\n", entries.len());
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        out.push_str(&format!("  {}\n", cells.join("  ").trim_end()));
+    }
+    let mismatches = entries
+        .iter()
+        .filter_map(|e| e.outcome.as_ref().ok())
+        .filter(|o| {
+            o.detected_matrix
+                .is_some_and(|detected| !o.advertised_matrix.starts_with(detected))
+        })
+        .count();
+    if chart {
+        out.push_str(&format!(
+            "\n{mismatches} capture(s) where the chart-detected matrix differs from the advertised one.\n"
+        ));
+    } else {
+        out.push_str(
+            "\nDetected needs --colorchecker or --colorbars; otherwise compare the BMPs in each directory.\n",
+        );
+    }
+    out
+}
+
+/// Replace the frame with the mean of all captured frames, reporting temporal noise first.
+fn average_frames(frame: &mut CapturedFrame, expected_size: usize) -> anyhow::Result<()> {
+    let mut frames: Vec<&[u8]> = Vec::with_capacity(frame.earlier_frames.len() + 1);
+    for data in frame.earlier_frames.iter().chain([&frame.data]) {
+        if data.len() < expected_size {
+            anyhow::bail!(
+                "Buffer too small in multi-frame capture: got {} bytes, expected at least {}",
+                data.len(),
+                expected_size
+            );
+        }
+        frames.push(&data[..expected_size]);
+    }
+
+    let noise = temporal::nv12_noise(&frames, frame.width, frame.height);
+    println!();
+    println!(
+        "Temporal noise over {} frames (RMS standard deviation, code values):",
+        noise.frames
+    );
+    let levels: Vec<String> = temporal::LEVEL_NAMES
+        .iter()
+        .zip(noise.y_by_level)
+        .filter_map(|(name, v)| v.map(|v| format!("{name} {v:.2}")))
+        .collect();
+    println!("  Y   {:.2}  ({})", noise.y, levels.join(", "));
+    println!("  Cb  {:.2}", noise.cb);
+    println!("  Cr  {:.2}", noise.cr);
+    println!(
+        "Averaged {} frames; noise in the decoded frame is about 1/{:.1} of a single frame.",
+        noise.frames,
+        (noise.frames as f64).sqrt()
+    );
+    info!("  (Motion in the scene shows up as noise and blurs the average.)");
+
+    frame.data = temporal::average(&frames);
+    frame.earlier_frames.clear();
+    Ok(())
+}
+
+/// A chart measurement: which chart, and how well each candidate matrix fits it.
+struct ChartVerdict {
+    chart: &'static str,
+    report: chart::ChartReport,
+}
+
+/// Sample a reference chart, located by hand or automatically, and score each
+/// candidate matrix by ΔE2000.
+fn run_chart(
+    frame: &CapturedFrame,
+    arg: &cli::ChartArg,
+    device_index: Option<usize>,
+    files: &OutputFiles,
+) -> anyhow::Result<ChartVerdict> {
+    let layout = arg.layout;
+    let homography = match arg.corners {
+        Some(corners) => geometry::Homography::from_unit_square(corners),
+        None => {
+            let found = detect::find_chart(
+                layout,
+                &frame.data,
+                frame.width,
+                frame.height,
+                frame.full_range,
+            )
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Could not locate a {} in the frame. Fill more of the frame with the chart, \
+                     light it evenly, or pass its corners explicitly.",
+                    layout.name
+                )
+            })?;
+            let corners = found
+                .chart
+                .corners()
+                .map(|p| format!("{:.0},{:.0}", p.x, p.y))
+                .join(",");
+            println!();
+            println!(
+                "Detected {}: {} of {} patches located, corners {}",
+                layout.name,
+                found.patches_found,
+                layout.patches.len(),
+                corners
+            );
+            found.chart
+        }
+    };
+    let samples =
+        chart::sample_patches(layout, &frame.data, frame.width, frame.height, &homography);
+    let outside = chart::patches_outside(layout, &samples);
+    if !outside.is_empty() {
+        anyhow::bail!(
+            "{} of the {}'s patches fall wholly or partly outside the {}x{} frame ({}). Check the corners.",
+            outside.len(),
+            layout.name,
+            frame.width,
+            frame.height,
+            outside.join(", ")
+        );
+    }
+    let report = chart::evaluate(layout, samples, frame.full_range);
+
+    println!();
+    println!(
+        "{} Delta E 2000 (decoded as {} range):",
+        layout.name,
+        if frame.full_range { "full" } else { "limited" }
+    );
+    print!("  {:>2}  {:<15}", "#", "Patch");
+    for score in &report.scores {
+        print!(" {:>8}", score.matrix.name);
+    }
+    println!();
+
+    for (i, (patch, sample)) in layout.patches.iter().zip(&report.samples).enumerate() {
+        print!("  {:>2}  {:<15}", i + 1, patch.name);
+        for score in &report.scores {
+            print!(" {:>8.2}", score.delta_e[i]);
+        }
+        if !sample.is_uniform() {
+            print!(
+                "  <-- non-uniform (Y stddev {:.1}), check corners",
+                sample.y_stddev
+            );
+        }
+        println!();
+    }
+
+    print!("  {:>2}  {:<15}", "", "Mean");
+    for score in &report.scores {
+        print!(" {:>8.2}", score.mean);
+    }
+    println!();
+    print!("  {:>2}  {:<15}", "", "Max");
+    for score in &report.scores {
+        print!(" {:>8.2}", score.max);
+    }
+    println!();
+
+    let best = report.best();
     println!();
-    println!("Compare the two images side by side:");
-    println!("  - The image with correct colors reveals which matrix the firmware uses.");
-    println!("  - If capture_bt601.bmp looks correct, firmware encodes BT.601.");
-    println!("  - If capture_bt709.bmp looks correct, firmware encodes BT.709.");
+    println!(
+        "Verdict: {} minimises error (mean Delta E {:.2}).",
+        best.matrix.name, best.mean
+    );
+    for score in report
+        .scores
+        .iter()
+        .filter(|s| s.matrix.name != best.matrix.name)
+    {
+        println!("  {}: mean Delta E {:.2}", score.matrix.name, score.mean);
+    }
+    info!("  Absolute Delta E also includes exposure and white balance error;");
+    info!("  the ranking between matrices is what identifies the firmware's matrix.");
+    if let Some(index) = device_index.filter(|_| frame.usb.is_some()) {
+        if [yuv::BT601.name, yuv::BT709.name].contains(&best.matrix.name)
+            && best.matrix.name != frame.colorspace.matrix
+        {
+            println!(
+                "  The driver reports {}. To record this in the quirks database:",
+                frame.colorspace.matrix
+            );
+            println!(
+                "    webcam-colorspace --device {} add-quirk {}",
+                index + 1,
+                best.matrix.name.replace('.', "").to_lowercase()
+            );
+        }
+    }
+
+    // Overlay the sampled regions on the winning decode so corner placement can be checked
+    let rgb = yuv::nv12_to_rgb24(
+        &frame.data,
+        frame.width,
+        frame.height,
+        best.matrix,
+        frame.full_range,
+    );
+    let mut overlay = canvas::Canvas::from_rgb(frame.width, frame.height, rgb);
+    for sample in &report.samples {
+        let color = if sample.is_uniform() {
+            [0, 255, 0]
+        } else {
+            [255, 0, 0]
+        };
+        for k in 0..4 {
+            let (a, b) = (sample.region[k], sample.region[(k + 1) % 4]);
+            overlay.line(a.x as i64, a.y as i64, b.x as i64, b.y as i64, color);
+        }
+    }
+    let path = files.path("capture_chart", "bmp");
+    bmp::write_bmp(&path, overlay.width, overlay.height, &overlay.data)?;
+    println!();
+    println!("Saved sampled patch overlay: {}", path.display());
+
+    Ok(ChartVerdict {
+        chart: layout.name,
+        report,
+    })
+}
+
+/// Render waveform, parade and vectorscope images from the raw YUV planes.
+fn write_scopes(frame: &CapturedFrame, files: &OutputFiles) -> anyhow::Result<()> {
+    info!();
+    info!("Rendering scopes...");
+
+    let (data, w, h) = (&frame.data, frame.width, frame.height);
+    let images = [
+        scope::luma_waveform(data, w, h),
+        scope::rgb_parade(data, w, h, &yuv::BT601, frame.full_range),
+        scope::rgb_parade(data, w, h, &yuv::BT709, frame.full_range),
+        scope::vectorscope(data, w, h, frame.full_range),
+    ];
+
+    for (name, image) in SCOPE_OUTPUTS.iter().zip(&images) {
+        let path = files.path(name, "bmp");
+        bmp::write_bmp(&path, image.width, image.height, &image.data)?;
+        println!("Saved: {}", path.display());
+    }
+
+    info!();
+    info!("In the vectorscope, colour bar or chart targets should land on the boxes of");
+    info!("the matrix the firmware encodes. A matrix mismatch rotates and scales them");
+    info!("between the orange (BT.601) and blue (BT.709) targets.");
 
     Ok(())
 }
 
-fn run_force_matrix(device_index: usize, matrix: cli::MatrixChoice) -> anyhow::Result<()> {
+/// Decode every matrix × range combination into one labelled contact sheet.
+fn run_decode_grid(frame: &CapturedFrame, mirror: bool, files: &OutputFiles) -> anyhow::Result<()> {
+    info!();
+    info!("Decoding matrix x range grid...");
+
+    let mut variants = grid::decode_variants(&frame.data, frame.width, frame.height);
+    if mirror {
+        for v in &mut variants {
+            mirror_rgb(&mut v.image.data, frame.width, frame.height);
+        }
+    }
+
+    println!();
+    println!(
+        "  {:<18} {:>12} {:>13}",
+        "Variant", "Clipped low", "Clipped high"
+    );
+    for v in &variants {
+        let reported = if v.full_range == frame.full_range {
+            "  <-- reported range"
+        } else {
+            ""
+        };
+        println!(
+            "  {:<18} {:>11.2}% {:>12.2}%{}",
+            v.label(),
+            v.clip.low_percent(),
+            v.clip.high_percent(),
+            reported
+        );
+    }
+    info!();
+    info!("  Heavy clipping usually means the range assumption is wrong; the");
+    info!("  matrix changes hue, so compare skin tones and neutral greys across columns.");
+
+    let sheet = grid::contact_sheet(&variants, &frame.colorspace.range);
+    let path = files.path("capture_grid", "bmp");
+    bmp::write_bmp(&path, sheet.width, sheet.height, &sheet.data)?;
+    println!();
+    println!("Saved: {}", path.display());
+
+    Ok(())
+}
+
+/// Everything about one frame in a single HTML file: where it came from, what the
+/// driver advertises, the decodes and plots, and the automated verdict.
+fn write_report(
+    args: &cli::AnalysisArgs,
+    frame: &CapturedFrame,
+    files: &OutputFiles,
+    id: &platform::DeviceId,
+    from_camera: bool,
+    analysis: &FrameAnalysis,
+) -> anyhow::Result<()> {
+    info!();
+    info!("Writing report...");
+
+    // The full enumeration entry, if the frame came from a camera
+    let device = if from_camera {
+        platform::enumerate_devices()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|d| d.path == id.path)
+    } else {
+        None
+    };
+    let hist = histogram::nv12_histograms(&frame.data, frame.width, frame.height);
+    let stats = hist.range_stats();
+
+    let mut report = report::Report::new(&format!("Colorspace report: {}", id.name));
+    report.facts(&[
+        ("OS", os_description()),
+        (
+            "Generated by",
+            format!("webcam-colorspace {}", env!("CARGO_PKG_VERSION")),
+        ),
+        ("Time", output::timestamp(std::time::SystemTime::now())),
+    ]);
+
+    report.heading("Source");
+    let mut source = vec![("Name", id.name.clone()), ("Path", id.path.clone())];
+    if let Some(usb) = &frame.usb {
+        source.push(("USB", usb.to_string()));
+    }
+    if let Some(quirk) = device.as_ref().and_then(|d| d.quirk.as_ref()) {
+        source.push((
+            "Known quirk",
+            format!("{}: {}", quirk.device, quirk.summary()),
+        ));
+    }
+    source.extend([
+        (
+            "Frame",
+            format!("{} {}x{}", frame.pixel_format, frame.width, frame.height),
+        ),
+        ("Primaries", frame.colorspace.primaries.clone()),
+        ("YUV matrix", frame.colorspace.matrix.clone()),
+        ("Transfer", frame.colorspace.transfer.clone()),
+        ("Range", frame.colorspace.range.clone()),
+    ]);
+    report.facts(&source);
+
+    report.heading("Verdict");
+    let mut verdict = Vec::new();
+    match &analysis.chart {
+        Some(chart) => {
+            let best = chart.report.best();
+            let others: Vec<String> = chart
+                .report
+                .scores
+                .iter()
+                .filter(|s| s.matrix.name != best.matrix.name)
+                .map(|s| format!("{} {:.2}", s.matrix.name, s.mean))
+                .collect();
+            verdict.push(format!(
+                "Matrix: {} measured against the {} fits best (mean Delta E {:.2}; {}).",
+                best.matrix.name,
+                chart.chart,
+                best.mean,
+                others.join(", ")
+            ));
+            verdict.push(if best.matrix.name == frame.colorspace.matrix {
+                "The driver reports the same matrix.".to_string()
+            } else {
+                format!(
+                    "The driver reports {}: the firmware and the driver disagree.",
+                    frame.colorspace.matrix
+                )
+            });
+        }
+        None => verdict.push(format!(
+            "Matrix: not measured; the driver reports {}. Compare the decodes below by eye, \
+             or capture a chart with --colorchecker or --colorbars.",
+            frame.colorspace.matrix
+        )),
+    }
+    verdict.push(format!(
+        "Range: the driver reports {}; the samples look {} ({:.2}% of Y outside 16-235, \
+         {:.2}% of Cb/Cr outside 16-240).",
+        frame.colorspace.range,
+        analysis.inferred_range.label(),
+        stats.y_outside_fraction() * 100.0,
+        stats.c_outside_fraction() * 100.0
+    ));
+    let reported_range = if frame.full_range {
+        histogram::RangeInference::Full
+    } else {
+        histogram::RangeInference::Limited
+    };
+    if analysis.inferred_range != histogram::RangeInference::Inconclusive
+        && analysis.inferred_range != reported_range
+    {
+        verdict.push("The driver's nominal range attribute is probably wrong.".to_string());
+    }
+    if let Some(device) = &device {
+        verdict.extend(device_problems(device));
+    }
+    report.list(&verdict);
+
+    if let Some(device) = &device {
+        report.heading("Formats");
+        let mut seen = std::collections::HashSet::new();
+        let rows: Vec<Vec<String>> = device
+            .formats
+            .iter()
+            .filter(|f| {
+                seen.insert(format!(
+                    "{}|{}|{}",
+                    f.pixel_format, f.resolution, f.frame_rates
+                ))
+            })
+            .map(|f| {
+                vec![
+                    f.pixel_format.clone(),
+                    f.resolution.clone(),
+                    f.frame_rates.to_string(),
+                    f.colorspace.primaries.clone(),
+                    f.colorspace.matrix.clone(),
+                    f.colorspace.transfer.clone(),
+                    f.colorspace.range.clone(),
+                ]
+            })
+            .collect();
+        report.table(
+            &[
+                "Format",
+                "Size",
+                "Frame rates",
+                "Primaries",
+                "YUV matrix",
+                "Transfer",
+                "Range",
+            ],
+            &rows,
+        );
+
+        if !device.uvc_formats.is_empty() {
+            report.heading("UVC descriptors");
+            let rows: Vec<Vec<String>> = device
+                .uvc_formats
+                .iter()
+                .map(|f| {
+                    vec![
+                        format!(
+                            "{} (format {}, interface {})",
+                            f.pixel_format, f.index, f.interface
+                        ),
+                        f.frame_sizes.join(", "),
+                        if f.color_matching {
+                            "Color Matching".to_string()
+                        } else {
+                            "UVC defaults".to_string()
+                        },
+                        f.primaries.clone(),
+                        f.transfer.clone(),
+                        f.matrix.clone(),
+                        f.reported_matrix
+                            .clone()
+                            .unwrap_or_else(|| "format not listed".to_string()),
+                    ]
+                })
+                .collect();
+            report.table(
+                &[
+                    "Format",
+                    "Sizes",
+                    "Source",
+                    "Primaries",
+                    "Transfer",
+                    "Matrix",
+                    "Driver reports",
+                ],
+                &rows,
+            );
+        }
+    }
+
+    report.heading("Decodes");
+    report.paragraph(&format!(
+        "The frame decoded with each matrix, as {} range. The one with correct colours \
+         reveals the matrix the firmware encodes.",
+        if frame.full_range { "full" } else { "limited" }
+    ));
+    let mut decodes = Vec::new();
+    for matrix in yuv::CANDIDATE_MATRICES {
+        let mut rgb = yuv::nv12_to_rgb24(
+            &frame.data,
+            frame.width,
+            frame.height,
+            matrix,
+            frame.full_range,
+        );
+        if args.mirror {
+            mirror_rgb(&mut rgb, frame.width, frame.height);
+        }
+        let image = canvas::Canvas::from_rgb(frame.width, frame.height, rgb);
+        report.image(matrix.name, &image);
+        decodes.push((matrix.name, image));
+    }
+
+    report.heading("Analysis");
+    if let (Some((_, a)), Some((_, b))) = (
+        decodes.iter().find(|(name, _)| *name == yuv::BT601.name),
+        decodes.iter().find(|(name, _)| *name == yuv::BT709.name),
+    ) {
+        report.image(
+            "Difference between the BT.601 and BT.709 decodes",
+            &grid::difference_heatmap(a, b),
+        );
+    }
+    report.image("Histograms", &scope::histogram_plot(&hist));
+    report.image(
+        "Vectorscope",
+        &scope::vectorscope(&frame.data, frame.width, frame.height, frame.full_range),
+    );
+    report.image(
+        "Luma waveform",
+        &scope::luma_waveform(&frame.data, frame.width, frame.height),
+    );
+
+    let path = files.path("report", "html");
+    report.write(&path)?;
+    println!("Saved report: {}", path.display());
+    Ok(())
+}
+
+/// Mean difference per sample above which no decode counts as a match.
+const OS_MATCH_LIMIT: f64 = 6.0;
+/// Lead the best decode needs over the next one to be conclusive.
+const OS_MATCH_MARGIN: f64 = 0.5;
+
+/// Rank our decodes of the raw frame against the OS's RGB conversion of the same scene,
+/// which reveals the matrix and range the OS pipeline really converts with.
+fn compare_os_conversion(
+    frame: &CapturedFrame,
+    mut os_rgb: Vec<u8>,
+    mirror: bool,
+    files: &OutputFiles,
+) -> anyhow::Result<()> {
+    let variants = grid::decode_variants(&frame.data, frame.width, frame.height);
+    let ranked = grid::rank_variants(&variants, &os_rgb);
+
+    println!();
+    println!("OS RGB conversion against our decodes of the raw frame:");
+    for (variant, difference) in &ranked {
+        println!(
+            "  {:<16} mean difference {:.2}",
+            variant.label(),
+            difference
+        );
+    }
+    let (best, difference) = ranked[0];
+    let advertised = &frame.colorspace.matrix;
+    if difference > OS_MATCH_LIMIT {
+        println!(
+            "Verdict: none of our decodes matches (best {:.2}); the scene or exposure probably changed between the captures.",
+            difference
+        );
+        info!("  Pin exposure and white balance (--exposure, --white-balance) and try again.");
+    } else if ranked[1].1 - difference < OS_MATCH_MARGIN {
+        println!(
+            "Verdict: inconclusive; {} and {} match almost equally well.",
+            best.label(),
+            ranked[1].0.label()
+        );
+        info!("  Point the camera at something with saturated colours and try again.");
+    } else if advertised.starts_with(best.matrix.name) && best.full_range == frame.full_range {
+        println!(
+            "Verdict: the OS converts with {}, as the driver advertises.",
+            best.label()
+        );
+    } else {
+        println!(
+            "Verdict: the OS converts with {}, but the driver advertises {} {}.",
+            best.label(),
+            advertised,
+            if frame.full_range { "full" } else { "limited" }
+        );
+    }
+
+    if mirror {
+        mirror_rgb(&mut os_rgb, frame.width, frame.height);
+    }
+    let path = files.path("capture_os", "bmp");
+    bmp::write_bmp(&path, frame.width, frame.height, &os_rgb)?;
+    println!("Saved: {}", path.display());
+    Ok(())
+}
+
+/// What the quirks database knows about the captured camera, if anything.
+fn print_known_quirk(frame: &CapturedFrame) {
+    let Some(usb) = &frame.usb else {
+        return;
+    };
+    if let Some(quirk) = quirks::Database::load().lookup(usb) {
+        println!("Known quirk ({}): {}", quirk.device, quirk.summary());
+    }
+}
+
+/// Check the reported nominal range against the actual sample distribution.
+fn print_range_analysis(frame: &CapturedFrame) -> histogram::RangeInference {
+    let hist = histogram::nv12_histograms(&frame.data, frame.width, frame.height);
+    let stats = hist.range_stats();
+    let inferred = hist.infer_range();
+
+    println!();
+    println!("Range analysis:");
+    println!(
+        "  Y  outside 16-235: {:>6.2}% ({} below, {} above; min {}, max {})",
+        stats.y_outside_fraction() * 100.0,
+        stats.y_below,
+        stats.y_above,
+        stats.y_min,
+        stats.y_max
+    );
+    println!(
+        "  Cb/Cr outside 16-240: {:>6.2}% ({} below, {} above)",
+        stats.c_outside_fraction() * 100.0,
+        stats.c_below,
+        stats.c_above
+    );
+    println!("  Reported range: {}", frame.colorspace.range);
+    println!("  Inferred range: {}", inferred.label());
+
+    let reported = if frame.full_range {
+        histogram::RangeInference::Full
+    } else {
+        histogram::RangeInference::Limited
+    };
+    if inferred != histogram::RangeInference::Inconclusive && inferred != reported {
+        println!(
+            "  WARNING: samples look {} but decoding will use {}.",
+            inferred.label(),
+            reported.label()
+        );
+        println!("  The driver's nominal range attribute is probably wrong.");
+    } else if inferred == histogram::RangeInference::Inconclusive {
+        info!(
+            "  (Scene doesn't reach the range limits; point the camera at bright and dark areas.)"
+        );
+    }
+    println!();
+    inferred
+}
+
+fn run_mjpg_test(args: &cli::CaptureTestArgs) -> anyhow::Result<()> {
+    print_header();
+
+    let (device_index, id) = platform::select_device(&args.device)?;
+    let frame = platform::capture_frame(&CaptureRequest {
+        device_index,
+        resolution: args.resolution,
+        pixel_format: "MJPG",
+        frames: 1,
+        settle: args.settle,
+        controls: &args.controls,
+    })?;
+
+    println!(
+        "Captured {} frame: {}x{} ({} bytes)",
+        frame.pixel_format,
+        frame.width,
+        frame.height,
+        frame.data.len()
+    );
+    print_known_quirk(&frame);
+
+    if args.save_raw {
+        let files = output_files(
+            &args.analysis,
+            args.analysis.output_dir.clone().unwrap_or_default(),
+            &id.name,
+            &frame,
+        );
+        files.prepare(&[("capture_raw", "jpg")])?;
+        let raw_path = files.path("capture_raw", "jpg");
+        std::fs::write(&raw_path, &frame.data)?;
+        println!("Saved raw MJPG frame: {}", raw_path.display());
+    }
+
+    let info = jpeg::parse_markers(&frame.data)?;
+
+    println!();
+    println!("Driver-reported colorspace:");
+    print_colorspace(&frame.colorspace);
+
+    println!();
+    println!("JPEG bitstream markers:");
+    match &info.jfif {
+        Some(jfif) => println!(
+            "  JFIF APP0: version {}.{:02}",
+            jfif.version.0, jfif.version.1
+        ),
+        None => println!("  JFIF APP0: not present"),
+    }
+    match &info.adobe {
+        Some(adobe) => {
+            let transform = match adobe.transform {
+                0 => "0 (no transform: RGB/CMYK)",
+                1 => "1 (YCbCr)",
+                2 => "2 (YCCK)",
+                _ => "unknown",
+            };
+            println!(
+                "  Adobe APP14: version {}, transform {}",
+                adobe.version, transform
+            );
+        }
+        None => println!("  Adobe APP14: not present"),
+    }
+    match info.exif_colorspace {
+        Some(v) => println!("  EXIF ColorSpace: {}", jpeg::exif_colorspace_name(v)),
+        None => println!("  EXIF ColorSpace: not present"),
+    }
+    match &info.icc {
+        Some(icc) => println!(
+            "  ICC profile: {} bytes, v{}.{}, data colour space '{}'{}",
+            icc.size,
+            icc.version.0,
+            icc.version.1,
+            icc.color_space.trim(),
+            icc.description
+                .as_ref()
+                .map(|d| format!(", \"{d}\""))
+                .unwrap_or_default()
+        ),
+        None => println!("  ICC profile: not present"),
+    }
+    let ids: Vec<String> = info.components.iter().map(|c| c.to_string()).collect();
+    println!(
+        "  Components: {} (IDs {})",
+        info.components.len(),
+        ids.join(", ")
+    );
+
+    let encoding = info.implied_encoding();
+    println!();
+    println!(
+        "Decoders will interpret this frame as: {}",
+        encoding.description()
+    );
+
+    let contradictions = mjpg_contradictions(&frame, encoding);
+    println!();
+    if contradictions.is_empty() {
+        println!("No contradictions between driver attributes and JPEG markers.");
+    } else {
+        println!("Contradictions:");
+        for c in &contradictions {
+            println!("  ! {c}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare what the driver advertises against what a JPEG decoder will assume.
+fn mjpg_contradictions(frame: &CapturedFrame, encoding: jpeg::JpegEncoding) -> Vec<String> {
+    let cs = &frame.colorspace;
+    let mut found = Vec::new();
+
+    match encoding {
+        jpeg::JpegEncoding::YCbCrBt601Full => {
+            if cs.matrix != "BT.601" && cs.matrix != "Not specified" {
+                found.push(format!(
+                    "Driver says YUV matrix {} but the JPEG data implies BT.601.",
+                    cs.matrix
+                ));
+            }
+            if cs.range.starts_with("Limited") {
+                found.push(format!(
+                    "Driver says range {} but JPEG YCbCr is full range (0-255).",
+                    cs.range
+                ));
+            }
+        }
+        jpeg::JpegEncoding::Rgb => {
+            if cs.matrix != "Not specified" {
+                found.push(format!(
+                    "Driver says YUV matrix {} but the JPEG stores RGB components.",
+                    cs.matrix
+                ));
+            }
+        }
+        jpeg::JpegEncoding::Grayscale | jpeg::JpegEncoding::Unknown => {}
+    }
+
+    found
+}
+
+fn print_colorspace(cs: &platform::ColorspaceInfo) {
+    println!("  Primaries: {}", cs.primaries);
+    println!("  YUV Matrix: {}", cs.matrix);
+    println!("  Transfer: {}", cs.transfer);
+    println!("  Range: {}", cs.range);
+}
+
+/// Writes a recording to Y4M as it arrives and keeps each frame's statistics.
+struct RecordWriter<'a> {
+    args: &'a cli::RecordArgs,
+    device_name: &'a str,
+    /// Created once the stream's format is known.
+    output: Option<(y4m::Y4mWriter, OutputFiles, (u32, u32))>,
+    frame_rate: Option<platform::Rational>,
+    stats: Vec<temporal::FrameStats>,
+}
+
+impl platform::RecordSink for RecordWriter<'_> {
+    fn start(&mut self, format: &platform::StreamFormat) -> anyhow::Result<()> {
+        let size = (format.width, format.height);
+        let files = OutputFiles::new(
+            self.args.output_dir.clone().unwrap_or_default(),
+            self.args
+                .name_template
+                .as_deref()
+                .unwrap_or(output::DEFAULT_TEMPLATE),
+            self.args.force,
+            self.device_name,
+            size,
+            &format.colorspace.matrix,
+            format.full_range,
+        );
+        files.prepare(&[("record", "y4m"), ("record_stats", "csv")])?;
+        let writer = y4m::Y4mWriter::create(
+            &files.path("record", "y4m"),
+            format.width,
+            format.height,
+            format.frame_rate.map(|r| (r.num, r.den)),
+            format.full_range,
+        )?;
+        print_colorspace(&format.colorspace);
+        info!(
+            "Recording for {} s once the camera has settled...",
+            self.args.duration.as_secs()
+        );
+        self.output = Some((writer, files, size));
+        self.frame_rate = format.frame_rate;
+        Ok(())
+    }
+
+    fn frame(&mut self, data: &[u8], timestamp: std::time::Duration) -> anyhow::Result<()> {
+        let (writer, _, (width, height)) = self
+            .output
+            .as_mut()
+            .context("Frame received before the stream started")?;
+        writer.write_frame(data)?;
+        self.stats
+            .push(temporal::frame_stats(data, *width, *height, timestamp));
+        Ok(())
+    }
+}
+
+/// Record a clip, then look for frames where the colour processing changes suddenly.
+/// Returns the number of colour and range changes found.
+fn run_record(args: &cli::RecordArgs) -> anyhow::Result<usize> {
     print_header();
+
+    let (device_index, id) = platform::select_device(&args.device)?;
+    let mut sink = RecordWriter {
+        args,
+        device_name: &id.name,
+        output: None,
+        frame_rate: None,
+        stats: Vec::new(),
+    };
+    let result = platform::record(
+        &CaptureRequest {
+            device_index,
+            resolution: args.resolution,
+            pixel_format: "NV12",
+            frames: 0,
+            settle: args.settle,
+            controls: &args.controls,
+        },
+        args.duration,
+        &mut sink,
+    );
+    // Keep what was recorded even if the stream failed partway
+    let Some((writer, files, _)) = sink.output else {
+        return result.map(|()| 0);
+    };
+    let frames = writer.finish()?;
+    let stats = sink.stats;
+    let video_path = files.path("record", "y4m");
+    let stats_path = files.path("record_stats", "csv");
+    let mut csv = String::from("frame,time_s,y,cb,cr,saturation,y_outside_percent\n");
+    for (i, s) in stats.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{:.6},{:.3},{:.3},{:.3},{:.3},{:.4}\n",
+            i + 1,
+            s.timestamp.as_secs_f64(),
+            s.y,
+            s.cb,
+            s.cr,
+            s.saturation,
+            s.y_outside
+        ));
+    }
+    std::fs::write(&stats_path, csv)
+        .with_context(|| format!("Failed to write {}", stats_path.display()))?;
+    if let Err(e) = result {
+        eprintln!("Error: {e:#}");
+        println!("Keeping the {frames} frame(s) recorded before the error.");
+    }
+    let (Some(first), Some(last)) = (stats.first(), stats.last()) else {
+        anyhow::bail!("No frames were recorded");
+    };
+
+    println!();
+    let elapsed = last.timestamp.saturating_sub(first.timestamp).as_secs_f64();
+    let measured = if elapsed > 0.0 {
+        format!("{:.2} fps", (frames - 1) as f64 / elapsed)
+    } else {
+        "rate unknown".to_string()
+    };
+    let nominal = sink
+        .frame_rate
+        .map(|r| format!("; the driver set {:.2} fps", r.value()))
+        .unwrap_or_default();
+    println!("Recorded {frames} frame(s) in {elapsed:.2} s ({measured}{nominal})");
+    println!("Saved: {}", video_path.display());
+    println!("Saved: {}", stats_path.display());
+
     println!();
+    println!("Per-frame statistics (min / max):");
+    let range = |f: fn(&temporal::FrameStats) -> f64| {
+        let values = stats.iter().map(f);
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        (min, values.fold(f64::NEG_INFINITY, f64::max))
+    };
+    for (name, (min, max)) in [
+        ("Y", range(|s| s.y)),
+        ("Cb", range(|s| s.cb)),
+        ("Cr", range(|s| s.cr)),
+        ("Saturation", range(|s| s.saturation)),
+        ("Y outside 16-235 %", range(|s| s.y_outside)),
+    ] {
+        println!("  {name:<20} {min:>7.2} / {max:>7.2}");
+    }
+
+    let changes = temporal::find_changes(&stats);
+    println!();
+    if changes.is_empty() {
+        println!("No sudden changes: colour processing stayed consistent throughout.");
+        return Ok(0);
+    }
+    println!("Sudden changes:");
+    for change in &changes {
+        let frames = if change.first_frame == change.last_frame {
+            format!("Frame {}", change.first_frame + 1)
+        } else {
+            format!(
+                "Frames {}-{}",
+                change.first_frame + 1,
+                change.last_frame + 1
+            )
+        };
+        let kind = match change.kind {
+            temporal::ChangeKind::Colour => "colour processing",
+            temporal::ChangeKind::Range => "range",
+            temporal::ChangeKind::Scene => "exposure or scene",
+            temporal::ChangeKind::Gap => "dropped frames",
+        };
+        println!(
+            "  {} ({:.2} s): {} — {}",
+            frames,
+            stats[change.first_frame].timestamp.as_secs_f64(),
+            kind,
+            change.description
+        );
+    }
+
+    let flagged: Vec<&temporal::Change> = changes
+        .iter()
+        .filter(|c| {
+            matches!(
+                c.kind,
+                temporal::ChangeKind::Colour | temporal::ChangeKind::Range
+            )
+        })
+        .collect();
+    println!();
+    if flagged.is_empty() {
+        println!("Verdict: no colour processing changes; the changes above come with luma changes or gaps.");
+        return Ok(0);
+    }
+    println!(
+        "Verdict: {} sudden change(s) in colour processing while the scene's brightness held steady.",
+        flagged.len()
+    );
+    info!("  Decode the frames either side to see which matrix or range each part uses, e.g.:");
+    let change = flagged[0];
+    for frame in [change.first_frame, change.last_frame + 1] {
+        info!(
+            "    webcam-colorspace decode {} --frame {} --grid",
+            video_path.display(),
+            frame
+        );
+    }
+    Ok(flagged.len())
+}
+
+fn run_force_matrix(
+    device: &cli::DeviceSelector,
+    matrix: cli::MatrixChoice,
+    capture: Option<&cli::ForceCapture>,
+) -> anyhow::Result<()> {
+    print_header();
+
+    let (device_index, id) = platform::select_device(device)?;
+    match capture {
+        Some(capture) => run_force_capture(device_index, &id, matrix, capture),
+        None => platform::force_matrix(device_index, matrix),
+    }
+}
+
+/// Capture with the matrix overridden and check which matrix the OS's own converter
+/// used, with and without the override, against our decodes of the same frame.
+fn run_force_capture(
+    device_index: usize,
+    id: &platform::DeviceId,
+    matrix: cli::MatrixChoice,
+    capture: &cli::ForceCapture,
+) -> anyhow::Result<()> {
+    let result = platform::force_matrix_capture(
+        &CaptureRequest {
+            device_index,
+            resolution: capture.resolution,
+            pixel_format: "NV12",
+            frames: 1,
+            settle: settle::SettlePolicy::default(),
+            controls: &[],
+        },
+        matrix,
+    )?;
+    let frame = &result.frame;
+    let (width, height) = (frame.width, frame.height);
 
-    platform::force_matrix(device_index, matrix)?;
+    let files = OutputFiles::new(
+        capture.output_dir.clone().unwrap_or_default(),
+        output::DEFAULT_TEMPLATE,
+        capture.force,
+        &id.name,
+        (width, height),
+        &frame.colorspace.matrix,
+        frame.full_range,
+    );
+    let forced_output = format!(
+        "os_forced_{}",
+        matrix.name().replace('.', "").to_lowercase()
+    );
+    let reported_label = format!("the driver's attributes ({})", frame.colorspace.matrix);
+    let forced_label = format!("the override ({})", matrix.name());
+    let mut conversions = vec![(&reported_label, "os_reported", &result.converted_reported)];
+    if let Some(rgb) = &result.converted_forced {
+        conversions.push((&forced_label, forced_output.as_str(), rgb));
+    }
+    let planned: Vec<(&str, &str)> = conversions.iter().map(|&(_, o, _)| (o, "bmp")).collect();
+    files.prepare(&planned)?;
 
+    let variants = grid::decode_variants(&frame.data, width, height);
+    let mut used = Vec::new();
+    println!();
+    println!("OS conversion (source reader video processing) against our decodes of the scene:");
+    for (label, output, rgb) in conversions {
+        let ranked = grid::rank_variants(&variants, rgb);
+        let (best, difference) = ranked[0];
+        println!(
+            "  Given {label}: matches {} (mean difference {:.2}; next {} at {:.2})",
+            best.label(),
+            difference,
+            ranked[1].0.label(),
+            ranked[1].1
+        );
+        let path = files.path(output, "bmp");
+        bmp::write_bmp(&path, width, height, rgb)?;
+        println!("    Saved: {}", path.display());
+        used.push(best.matrix.name);
+    }
+
+    println!();
+    match used.get(1) {
+        None => println!(
+            "Verdict: the camera's source doesn't keep the override, so the OS never sees it \
+             and still converts with {}.",
+            used[0]
+        ),
+        Some(&forced) if forced != matrix.name() => println!(
+            "Verdict: the OS converter ignores the override and still converts with {}.",
+            forced
+        ),
+        Some(_) if used[0] == matrix.name() => println!(
+            "Verdict: the OS already converts with {} without the override; it changes nothing.",
+            matrix.name()
+        ),
+        Some(_) => println!(
+            "Verdict: the OS converter honours the override: {} instead of {}.",
+            matrix.name(),
+            used[0]
+        ),
+    }
+    info!();
+    info!("The override lasts only for this session; other apps still get the driver's");
+    info!("attributes. Compare the images with capture_bt601.bmp/capture_bt709.bmp from");
+    info!("`capture`. A lasting fix has to come from the driver or firmware.");
     Ok(())
 }
 
 fn print_header() {
-    println!("webcam-colorspace — Camera Colorspace Diagnostic Tool");
-    println!("======================================================");
+    info!("webcam-colorspace — Camera Colorspace Diagnostic Tool");
+    info!("======================================================");
+    info!();
 }
 
 fn print_os_info() {
-    println!();
-    print!("OS: ");
+    println!("OS: {}", os_description());
+}
 
+fn os_description() -> String {
     #[cfg(windows)]
     {
-        if let Ok(output) = std::process::Command::new("cmd")
+        std::process::Command::new("cmd")
             .args(["/C", "ver"])
             .output()
-        {
-            let ver = String::from_utf8_lossy(&output.stdout);
-            let ver = ver.trim();
-            if !ver.is_empty() {
-                println!("{ver}");
-            } else {
-                println!("Windows (version unknown)");
-            }
-        } else {
-            println!("Windows (version unknown)");
-        }
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|ver| !ver.is_empty())
+            .unwrap_or_else(|| "Windows (version unknown)".to_string())
     }
 
     #[cfg(target_os = "linux")]
@@ -252,15 +2314,15 @@ fn print_os_info() {
             .unwrap_or_default();
 
         if kernel.is_empty() {
-            println!("{distro}");
+            distro
         } else {
-            println!("{distro} (kernel {kernel})");
+            format!("{distro} (kernel {kernel})")
         }
     }
 
     #[cfg(not(any(windows, target_os = "linux")))]
     {
-        println!("Unknown platform");
+        "Unknown platform".to_string()
     }
 }
 
@@ -274,13 +2336,13 @@ fn format_matrix_highlight(matrix: &str) -> String {
 }
 
 fn print_legend() {
-    println!("Legend");
-    println!("------");
-    println!("  YUV Matrix is the key diagnostic field:");
-    println!("    BT.709  = HD standard. Required by Windows 24H2+, Linux 720p+, ChromeOS.");
-    println!("    BT.601  = SD standard. Legacy; causes color shift if OS expects BT.709.");
-    println!("    Not specified = OS will pick a default. May vary by OS version.");
-    println!();
-    println!("  If the matrix shows 'Not specified' for all formats, the camera driver");
-    println!("  does not advertise colorspace info. The OS will apply its own default.");
+    info!("Legend");
+    info!("------");
+    info!("  YUV Matrix is the key diagnostic field:");
+    info!("    BT.709  = HD standard. Required by Windows 24H2+, Linux 720p+, ChromeOS.");
+    info!("    BT.601  = SD standard. Legacy; causes color shift if OS expects BT.709.");
+    info!("    Not specified = OS will pick a default. May vary by OS version.");
+    info!();
+    info!("  If the matrix shows 'Not specified' for all formats, the camera driver");
+    info!("  does not advertise colorspace info. The OS will apply its own default.");
 }
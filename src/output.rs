@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+/// Placeholders a `--name` template may use.
+const PLACEHOLDERS: [&str; 6] = [
+    "device",
+    "resolution",
+    "timestamp",
+    "matrix",
+    "range",
+    "output",
+];
+
+/// The template that reproduces the fixed names: capture_bt601.bmp, scope_waveform.bmp, ...
+pub const DEFAULT_TEMPLATE: &str = "{output}";
+
+/// Reject templates with unknown or unclosed placeholders.
+pub fn check_template(template: &str) -> anyhow::Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed '{{' in name template '{template}'"))?;
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            anyhow::bail!(
+                "Unknown placeholder '{{{name}}}' in name template. Expected one of: {}",
+                PLACEHOLDERS.map(|p| format!("{{{p}}}")).join(", ")
+            );
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Names and places the files of one capture: `--output-dir`, the `--name` template
+/// and `--force`.
+pub struct OutputFiles {
+    dir: PathBuf,
+    template: String,
+    force: bool,
+    /// Values for every placeholder except `{output}`.
+    fields: Vec<(&'static str, String)>,
+}
+
+impl OutputFiles {
+    /// `matrix` is the matrix the driver reports and `full_range` the range decoded with.
+    pub fn new(
+        dir: PathBuf,
+        template: &str,
+        force: bool,
+        device: &str,
+        (width, height): (u32, u32),
+        matrix: &str,
+        full_range: bool,
+    ) -> OutputFiles {
+        // A template without {output} would give every file the same name
+        let template = if template.contains("{output}") {
+            template.to_string()
+        } else {
+            format!("{template}_{{output}}")
+        };
+        OutputFiles {
+            dir,
+            template,
+            force,
+            fields: vec![
+                ("device", file_name_part(device)),
+                ("resolution", format!("{width}x{height}")),
+                ("timestamp", timestamp(SystemTime::now())),
+                (
+                    "matrix",
+                    file_name_part(&matrix.replace('.', "").to_lowercase()),
+                ),
+                (
+                    "range",
+                    if full_range { "full" } else { "limited" }.to_string(),
+                ),
+            ],
+        }
+    }
+
+    /// The path of one output, e.g. `("capture_bt601", "bmp")`.
+    pub fn path(&self, output: &str, extension: &str) -> PathBuf {
+        let mut name = self.template.clone();
+        for (placeholder, value) in &self.fields {
+            name = name.replace(&format!("{{{placeholder}}}"), value);
+        }
+        name = name.replace("{output}", output);
+        self.dir.join(format!("{name}.{extension}"))
+    }
+
+    /// Check none of the outputs exists (unless forced) and create their directories,
+    /// before anything is written.
+    pub fn prepare(&self, outputs: &[(&str, &str)]) -> anyhow::Result<()> {
+        for &(output, extension) in outputs {
+            let path = self.path(output, extension);
+            prepare_path(&path, self.force)?;
+        }
+        Ok(())
+    }
+}
+
+/// Refuse to overwrite `path` unless `force`, and create its directory.
+pub fn prepare_path(path: &Path, force: bool) -> anyhow::Result<()> {
+    if !force && path.exists() {
+        anyhow::bail!(
+            "{} already exists. Use --force to overwrite it, or choose another --output-dir or --name.",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// A name reduced to characters that are safe in a file name on every OS.
+pub fn file_name_part(name: &str) -> String {
+    let part: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let part = part.trim_matches('_');
+    if part.is_empty() {
+        "camera".to_string()
+    } else {
+        part.to_string()
+    }
+}
+
+/// UTC time as "20261018T093000Z", which sorts by time and is valid in file names.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
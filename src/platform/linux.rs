@@ -1,14 +1,48 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use anyhow::{Context, Result};
+use v4l::buffer::Type;
 use v4l::capability::Flags;
 use v4l::context;
+use v4l::control::{self as v4l_control, Control, Description, MenuItem, Value};
 use v4l::format::colorspace::Colorspace;
 use v4l::format::quantization::Quantization;
 use v4l::format::transfer::TransferFunction;
+use v4l::fraction::Fraction;
+use v4l::frameinterval::{FrameInterval, FrameIntervalEnum};
+use v4l::io::traits::CaptureStream;
 use v4l::prelude::*;
+use v4l::v4l2::{self, vidioc};
+use v4l::v4l_sys::{v4l2_format, v4l2_pix_format};
 use v4l::video::Capture;
-use v4l::FourCC;
+use v4l::{Format, FourCC};
+
+use super::{
+    CaptureRequest, CapturedFrame, ColorspaceInfo, ControlInfo, DeviceId, DeviceInfo, FormatInfo,
+    FrameCollector, FrameRates, MenuEntry, Rational, RecordSink, Recording, SizeRange,
+    StreamFormat, UsbInfo, UvcFormat,
+};
+use crate::controls::{self, CameraControl, ControlState};
+use crate::{uvc, yuv};
+
+// Control IDs from linux/v4l2-controls.h
+const CID_AUTO_WHITE_BALANCE: u32 = 0x0098_090c;
+const CID_AUTOGAIN: u32 = 0x0098_0912;
+const CID_GAIN: u32 = 0x0098_0913;
+const CID_WHITE_BALANCE_TEMPERATURE: u32 = 0x0098_091a;
+const CID_BACKLIGHT_COMPENSATION: u32 = 0x0098_091c;
+const CID_EXPOSURE_AUTO: u32 = 0x009a_0901;
+const CID_EXPOSURE_ABSOLUTE: u32 = 0x009a_0902;
 
-use super::{ColorspaceInfo, DeviceInfo, FormatInfo};
+// V4L2_CID_EXPOSURE_AUTO menu values. UVC cameras offer manual and aperture priority.
+const EXPOSURE_AUTO: i64 = 0;
+const EXPOSURE_MANUAL: i64 = 1;
+const EXPOSURE_APERTURE_PRIORITY: i64 = 3;
 
 pub fn enumerate_devices() -> Result<Vec<DeviceInfo>> {
     let nodes = context::enum_devices();
@@ -41,27 +75,30 @@ fn read_device(node: &context::Node) -> Result<Option<DeviceInfo>> {
 
     let name = caps.card.clone();
 
-    // Get current format for colorspace info
-    let current_fmt = dev.format().ok();
+    // Colorspace of the current format, for formats the driver won't try
+    let current_fmt = query_format(&dev, None).ok();
 
     let mut formats = Vec::new();
 
     for desc in dev.enum_formats().unwrap_or_default() {
         let framesizes = dev.enum_framesizes(desc.fourcc).unwrap_or_default();
 
+        // UVC reports colorspace per format, not per frame size, so one size will do
+        let first_size = framesizes.first().map(|f| match &f.size {
+            v4l::framesize::FrameSizeEnum::Discrete(d) => (d.width, d.height),
+            v4l::framesize::FrameSizeEnum::Stepwise(s) => (s.max_width, s.max_height),
+        });
+        let format_fmt = first_size
+            .and_then(|(w, h)| query_format(&dev, Some((desc.fourcc, w, h))).ok())
+            .or(current_fmt);
+
         for framesize in &framesizes {
-            let resolutions: Vec<(u32, u32)> = match &framesize.size {
-                v4l::framesize::FrameSizeEnum::Discrete(d) => {
-                    vec![(d.width, d.height)]
-                }
+            // Ranges can hold hundreds of thousands of sizes; only probe a few
+            let (resolutions, size_range) = match &framesize.size {
+                v4l::framesize::FrameSizeEnum::Discrete(d) => (vec![(d.width, d.height)], None),
                 v4l::framesize::FrameSizeEnum::Stepwise(s) => {
-                    let mut sizes = Vec::new();
-                    for w in (s.min_width..=s.max_width).step_by(s.step_width as usize) {
-                        for h in (s.min_height..=s.max_height).step_by(s.step_height as usize) {
-                            sizes.push((w, h));
-                        }
-                    }
-                    sizes
+                    let range = size_range(s);
+                    (range.probe_sizes(), Some(range))
                 }
             };
 
@@ -70,56 +107,690 @@ fn read_device(node: &context::Node) -> Result<Option<DeviceInfo>> {
                     .enum_frameintervals(desc.fourcc, width, height)
                     .unwrap_or_default();
 
-                let frame_rates: Vec<String> = intervals
-                    .iter()
-                    .filter_map(|fi| match &fi.interval {
-                        v4l::frameinterval::FrameIntervalEnum::Discrete(frac) => {
-                            if frac.numerator > 0 {
-                                Some(format!(
-                                    "{:.2} fps",
-                                    frac.denominator as f64 / frac.numerator as f64
-                                ))
-                            } else {
-                                None
-                            }
-                        }
-                        _ => None,
-                    })
-                    .collect();
-
-                let frame_rate = if frame_rates.is_empty() {
-                    "Unknown".to_string()
-                } else {
-                    frame_rates.join(", ")
-                };
-
-                let colorspace = current_fmt
-                    .as_ref()
-                    .map(|f| colorspace_info(f.colorspace, f.transfer, f.quantization))
-                    .unwrap_or_else(|| ColorspaceInfo {
-                        primaries: "Not available".to_string(),
-                        matrix: "Not available".to_string(),
-                        transfer: "Not available".to_string(),
-                        range: "Not available".to_string(),
-                    });
+                let frame_rates = frame_rates(&intervals);
+
+                let colorspace =
+                    format_fmt
+                        .as_ref()
+                        .map(pix_colorspace)
+                        .unwrap_or_else(|| ColorspaceInfo {
+                            primaries: "Not available".to_string(),
+                            matrix: "Not available".to_string(),
+                            transfer: "Not available".to_string(),
+                            range: "Not available".to_string(),
+                        });
 
                 formats.push(FormatInfo {
                     pixel_format: fourcc_name(desc.fourcc),
                     resolution: format!("{}x{}", width, height),
-                    frame_rate,
+                    size_range,
+                    frame_rates,
                     colorspace,
                 });
             }
         }
     }
 
+    let controls = match dev.query_controls() {
+        Ok(descriptions) => descriptions
+            .iter()
+            .filter_map(|desc| control_info(&dev, desc))
+            .collect(),
+        Err(e) => {
+            eprintln!("Warning: failed to query controls for {path}: {e}");
+            Vec::new()
+        }
+    };
+
+    let usb_device = usb_device_dir(&path);
+    let descriptors = usb_device
+        .as_deref()
+        .and_then(|dir| fs::read(dir.join("descriptors")).ok());
+    let uvc_formats = descriptors
+        .as_deref()
+        .map(|d| uvc_formats(d, &formats))
+        .unwrap_or_default();
+
     Ok(Some(DeviceInfo {
+        usb: usb_device
+            .as_deref()
+            .and_then(|dir| usb_info(dir, descriptors.as_deref())),
+        quirk: None,
         name,
         path,
         formats,
+        uvc_formats,
+        controls,
     }))
 }
 
+/// The sysfs directory of the USB device behind a video node. The node's `device` link
+/// points at the video interface; its parent is the USB device.
+fn usb_device_dir(node_path: &str) -> Option<PathBuf> {
+    let node = Path::new(node_path).file_name()?;
+    let interface = fs::canonicalize(
+        Path::new("/sys/class/video4linux")
+            .join(node)
+            .join("device"),
+    )
+    .ok()?;
+    interface.parent().map(Path::to_path_buf)
+}
+
+/// USB identity from the device's sysfs attributes and raw descriptors.
+fn usb_info(usb_device: &Path, descriptors: Option<&[u8]>) -> Option<UsbInfo> {
+    let attribute = |name: &str| {
+        fs::read_to_string(usb_device.join(name))
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let uvc_version = descriptors.and_then(uvc::uvc_version).map(uvc::bcd_version);
+
+    Some(UsbInfo {
+        vendor_id: attribute("idVendor")?,
+        product_id: attribute("idProduct")?,
+        bcd_device: attribute("bcdDevice"),
+        manufacturer: attribute("manufacturer"),
+        product: attribute("product"),
+        serial: attribute("serial"),
+        location: usb_device
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string()),
+        uvc_version,
+    })
+}
+
+/// The streaming formats the camera's descriptors declare, each compared with the matrix
+/// the driver reports for the same pixel format.
+fn uvc_formats(descriptors: &[u8], formats: &[FormatInfo]) -> Vec<UvcFormat> {
+    uvc::stream_formats(descriptors)
+        .into_iter()
+        .map(|format| {
+            let pixel_format = v4l2_fourcc(&format.fourcc);
+            let reported_matrix = formats
+                .iter()
+                .find(|f| f.pixel_format == pixel_format)
+                .map(|f| f.colorspace.matrix.clone());
+            let color = format.color();
+            let mismatch = match (color.matrix_family(), &reported_matrix) {
+                (Some(family), Some(reported)) => !reported.starts_with(family),
+                _ => false,
+            };
+            UvcFormat {
+                interface: format.interface,
+                index: format.index,
+                pixel_format,
+                frame_sizes: format
+                    .frame_sizes
+                    .iter()
+                    .map(|(w, h)| format!("{w}x{h}"))
+                    .collect(),
+                color_matching: format.color_matching.is_some(),
+                primaries: color.primaries_name(),
+                transfer: color.transfer_name(),
+                matrix: color.matrix_name(),
+                reported_matrix,
+                mismatch,
+            }
+        })
+        .collect()
+}
+
+/// The V4L2 pixel format uvcvideo maps a UVC format GUID to, where the codes differ.
+fn v4l2_fourcc(uvc_fourcc: &str) -> String {
+    match uvc_fourcc {
+        "YUY2" => "YUYV",
+        "Y800" => "GREY",
+        "I420" => "YU12",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Frame rates from `VIDIOC_ENUM_FRAMEINTERVALS`. Intervals are seconds per frame,
+/// so discrete ones are inverted into rates; stepwise ones are kept as intervals.
+fn frame_rates(intervals: &[FrameInterval]) -> FrameRates {
+    let rational = |f: Fraction| {
+        (f.numerator > 0 && f.denominator > 0).then_some(Rational {
+            num: f.numerator,
+            den: f.denominator,
+        })
+    };
+
+    let mut rates = Vec::new();
+    for interval in intervals {
+        match &interval.interval {
+            FrameIntervalEnum::Discrete(frac) => {
+                if let Some(interval) = rational(*frac) {
+                    rates.push(Rational {
+                        num: interval.den,
+                        den: interval.num,
+                    });
+                }
+            }
+            // A stepwise or continuous range is the only entry the driver reports
+            FrameIntervalEnum::Stepwise(s) => {
+                if let (Some(min_interval), Some(max_interval), Some(step)) =
+                    (rational(s.min), rational(s.max), rational(s.step))
+                {
+                    return FrameRates::Stepwise {
+                        min_interval,
+                        max_interval,
+                        step,
+                    };
+                }
+            }
+        }
+    }
+
+    if rates.is_empty() {
+        FrameRates::Unknown
+    } else {
+        FrameRates::Discrete(rates)
+    }
+}
+
+/// Describe one control from `VIDIOC_QUERY_EXT_CTRL`, with its current value.
+/// Class headings and disabled controls are skipped.
+fn control_info(dev: &Device, desc: &Description) -> Option<ControlInfo> {
+    use v4l_control::Flags as CtrlFlags;
+    use v4l_control::Type as CtrlType;
+
+    if desc.flags.contains(CtrlFlags::DISABLED) {
+        return None;
+    }
+    let kind = match desc.typ {
+        CtrlType::CtrlClass => return None,
+        CtrlType::Integer => "integer",
+        CtrlType::Boolean => "boolean",
+        CtrlType::Menu => "menu",
+        CtrlType::Button => "button",
+        CtrlType::Integer64 => "integer64",
+        CtrlType::String => "string",
+        CtrlType::Bitmask => "bitmask",
+        CtrlType::IntegerMenu => "integer menu",
+        CtrlType::U8 | CtrlType::U16 | CtrlType::U32 | CtrlType::Area => "compound",
+    };
+
+    let readable = !desc.flags.contains(CtrlFlags::WRITE_ONLY)
+        && matches!(
+            desc.typ,
+            CtrlType::Integer
+                | CtrlType::Boolean
+                | CtrlType::Menu
+                | CtrlType::Integer64
+                | CtrlType::IntegerMenu
+        );
+    let current = if readable {
+        match dev.control(desc.id) {
+            Ok(Control {
+                value: Value::Integer(v),
+                ..
+            }) => Some(v),
+            Ok(Control {
+                value: Value::Boolean(b),
+                ..
+            }) => Some(b as i64),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    let menu = desc
+        .items
+        .iter()
+        .flatten()
+        .map(|(index, item)| MenuEntry {
+            value: *index as i64,
+            name: match item {
+                MenuItem::Name(name) => name.clone(),
+                MenuItem::Value(v) => v.to_string(),
+            },
+        })
+        .collect();
+
+    let flags = [
+        (CtrlFlags::INACTIVE, "inactive"),
+        (CtrlFlags::READ_ONLY, "read-only"),
+        (CtrlFlags::WRITE_ONLY, "write-only"),
+        (CtrlFlags::VOLATILE, "volatile"),
+        (CtrlFlags::GRABBED, "grabbed"),
+    ]
+    .iter()
+    .filter(|(flag, _)| desc.flags.contains(*flag))
+    .map(|(_, name)| name.to_string())
+    .collect();
+
+    Some(ControlInfo {
+        id: desc.id,
+        name: desc.name.clone(),
+        kind: kind.to_string(),
+        minimum: desc.minimum,
+        maximum: desc.maximum,
+        step: desc.step,
+        default: desc.default,
+        current,
+        menu,
+        flags,
+    })
+}
+
+/// A device set up to stream the requested format.
+struct OpenStream {
+    dev: Device,
+    stride: u32,
+    /// YUYV captured for an NV12 request, to be repacked.
+    converted: bool,
+    format: StreamFormat,
+}
+
+impl OpenStream {
+    /// The frame in `bytes` as the request's pixel format; `None` if it is short.
+    fn frame(&self, bytes: &[u8], pixel_format: &str) -> Option<Vec<u8>> {
+        let (width, height) = (self.format.width, self.format.height);
+        if self.converted {
+            yuv::yuyv_to_nv12(bytes, width, height, self.stride)
+        } else if pixel_format == "NV12" {
+            yuv::nv12_unpad(bytes, width, height, self.stride)
+        } else {
+            Some(bytes.to_vec())
+        }
+    }
+}
+
+fn open_stream(request: &CaptureRequest) -> Result<OpenStream> {
+    let DeviceId { name, path, usb } = capture_device(request.device_index)?;
+    println!(
+        "Capturing from device {}: {} ({})",
+        request.device_index + 1,
+        name,
+        path
+    );
+    let dev = Device::with_path(&path).with_context(|| format!("Failed to open {path}"))?;
+
+    // Few UVC cameras offer NV12 on Linux; YUYV is captured and repacked instead
+    let candidates: Vec<FourCC> = match request.pixel_format {
+        "NV12" => vec![FourCC::new(b"NV12"), FourCC::new(b"YUYV")],
+        other => {
+            let code: [u8; 4] = other
+                .as_bytes()
+                .try_into()
+                .with_context(|| format!("Invalid pixel format '{other}'"))?;
+            vec![FourCC::new(&code)]
+        }
+    };
+    let (fourcc, width, height) = find_format(&dev, &candidates, request)?;
+
+    let format = dev
+        .set_format(&Format::new(width, height, fourcc))
+        .context("Failed to set capture format")?;
+    if format.fourcc != fourcc || format.width != width || format.height != height {
+        anyhow::bail!(
+            "Driver substituted {} {}x{} for the requested {} {}x{}",
+            fourcc_name(format.fourcc),
+            format.width,
+            format.height,
+            fourcc_name(fourcc),
+            width,
+            height
+        );
+    }
+    let converted = request.pixel_format == "NV12" && fourcc == FourCC::new(b"YUYV");
+    println!(
+        "Selected {} {}x{}{}",
+        fourcc_name(fourcc),
+        width,
+        height,
+        if converted { " (repacked to NV12)" } else { "" }
+    );
+
+    let ycbcr_enc = query_format(&dev, None)
+        .map(|pix| unsafe { pix.__bindgen_anon_1.ycbcr_enc })
+        .unwrap_or(0);
+    let colorspace = colorspace_info(
+        format.colorspace,
+        format.transfer,
+        format.quantization,
+        ycbcr_enc,
+    );
+    // V4L2 defaults Y'CbCr to limited range, except for the JPEG colorspace
+    let full_range = match format.quantization {
+        Quantization::FullRange => true,
+        Quantization::LimitedRange => false,
+        Quantization::Default => matches!(format.colorspace, Colorspace::JPEG),
+    };
+    if request.pixel_format != "MJPG" {
+        let range_label = if full_range {
+            "Full (0-255)"
+        } else {
+            "Limited (16-235)"
+        };
+        println!("Nominal range: {}", range_label);
+    }
+    // The frame interval, inverted into a rate
+    let frame_rate = dev
+        .params()
+        .ok()
+        .map(|params| params.interval)
+        .filter(|f| f.numerator > 0 && f.denominator > 0)
+        .map(|f| Rational {
+            num: f.denominator,
+            den: f.numerator,
+        });
+
+    Ok(OpenStream {
+        dev,
+        stride: format.stride,
+        converted,
+        format: StreamFormat {
+            usb,
+            width,
+            height,
+            full_range,
+            colorspace,
+            frame_rate,
+        },
+    })
+}
+
+pub fn capture_frame(request: &CaptureRequest) -> Result<CapturedFrame> {
+    let open = open_stream(request)?;
+    let dev = &open.dev;
+    let (width, height) = (open.format.width, open.format.height);
+
+    let (data, earlier_frames) = controls::with_controls(
+        request.controls,
+        |control| read_control(dev, control),
+        |control, state| write_control(dev, control, state),
+        || {
+            let mut stream = MmapStream::with_buffers(dev, Type::VideoCapture, 4)
+                .context("Failed to allocate capture buffers")?;
+            stream.set_timeout(Duration::from_secs(5));
+
+            let nv12 = request.pixel_format == "NV12";
+            let mut collector = FrameCollector::new(request, nv12.then_some((width, height)));
+            loop {
+                let (buf, meta) = stream.next().context("Failed to read a frame")?;
+                let bytes = &buf[..(meta.bytesused as usize).min(buf.len())];
+                // Short (corrupt) frames are dropped rather than fed to the analysis
+                if let Some(frame) = open.frame(bytes, request.pixel_format) {
+                    if collector.push(frame) {
+                        break;
+                    }
+                }
+            }
+            collector.finish().context("No frame received from camera")
+        },
+    )?;
+
+    let StreamFormat {
+        usb,
+        full_range,
+        colorspace,
+        ..
+    } = open.format;
+    Ok(CapturedFrame {
+        usb,
+        width,
+        height,
+        pixel_format: request.pixel_format.to_string(),
+        full_range,
+        colorspace,
+        data,
+        earlier_frames,
+    })
+}
+
+pub fn record(
+    request: &CaptureRequest,
+    duration: Duration,
+    sink: &mut dyn RecordSink,
+) -> Result<()> {
+    let open = open_stream(request)?;
+    let dev = &open.dev;
+    let mut recording = Recording::start(request, duration, &open.format, sink)?;
+
+    controls::with_controls(
+        request.controls,
+        |control| read_control(dev, control),
+        |control, state| write_control(dev, control, state),
+        || {
+            let mut stream = MmapStream::with_buffers(dev, Type::VideoCapture, 4)
+                .context("Failed to allocate capture buffers")?;
+            stream.set_timeout(Duration::from_secs(5));
+            loop {
+                let (buf, meta) = stream.next().context("Failed to read a frame")?;
+                let bytes = &buf[..(meta.bytesused as usize).min(buf.len())];
+                if let Some(frame) = open.frame(bytes, "NV12") {
+                    if recording.push(&frame, Duration::from(meta.timestamp))? {
+                        return Ok(());
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// V4L2 controls behind a camera control: the auto-mode switch, if it has one, and the value.
+fn control_ids(control: CameraControl) -> (Option<u32>, u32) {
+    match control {
+        CameraControl::Exposure => (Some(CID_EXPOSURE_AUTO), CID_EXPOSURE_ABSOLUTE),
+        CameraControl::WhiteBalance => {
+            (Some(CID_AUTO_WHITE_BALANCE), CID_WHITE_BALANCE_TEMPERATURE)
+        }
+        CameraControl::Gain => (Some(CID_AUTOGAIN), CID_GAIN),
+        CameraControl::BacklightCompensation => (None, CID_BACKLIGHT_COMPENSATION),
+    }
+}
+
+/// A control's value as an integer; `None` if the device doesn't have it.
+fn get_control(dev: &Device, id: u32) -> Result<Option<i64>> {
+    match dev.control(id) {
+        Ok(Control {
+            value: Value::Integer(v),
+            ..
+        }) => Ok(Some(v)),
+        Ok(Control {
+            value: Value::Boolean(b),
+            ..
+        }) => Ok(Some(b as i64)),
+        Ok(_) => anyhow::bail!("unexpected control type"),
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn set_control(dev: &Device, id: u32, value: Value) -> Result<()> {
+    dev.set_control(Control { id, value })?;
+    Ok(())
+}
+
+fn read_control(dev: &Device, control: CameraControl) -> Result<Option<ControlState>> {
+    let (mode_id, value_id) = control_ids(control);
+    let Some(value) = get_control(dev, value_id)? else {
+        return Ok(None);
+    };
+    let (auto, mode) = match mode_id {
+        Some(CID_EXPOSURE_AUTO) => {
+            let mode = get_control(dev, CID_EXPOSURE_AUTO)?;
+            (mode.is_some_and(|mode| mode != EXPOSURE_MANUAL), mode)
+        }
+        Some(id) => (get_control(dev, id)?.is_some_and(|on| on != 0), None),
+        None => (false, None),
+    };
+    Ok(Some(ControlState {
+        auto,
+        value: value as i32,
+        mode,
+    }))
+}
+
+/// Switch the auto mode first: drivers reject manual values while it is on.
+fn write_control(dev: &Device, control: CameraControl, state: ControlState) -> Result<()> {
+    let (mode_id, value_id) = control_ids(control);
+    let mode_id = match mode_id {
+        Some(id) if get_control(dev, id)?.is_some() => Some(id),
+        _ => None,
+    };
+    match (mode_id, state.auto, state.mode) {
+        (None, true, _) => anyhow::bail!("this device has no automatic mode for it"),
+        (None, false, _) => {}
+        // A saved state names the exact exposure mode; put back that one
+        (Some(CID_EXPOSURE_AUTO), _, Some(mode)) => {
+            set_control(dev, CID_EXPOSURE_AUTO, Value::Integer(mode))?
+        }
+        (Some(CID_EXPOSURE_AUTO), true, None) => set_control(
+            dev,
+            CID_EXPOSURE_AUTO,
+            Value::Integer(EXPOSURE_APERTURE_PRIORITY),
+        )
+        .or_else(|_| set_control(dev, CID_EXPOSURE_AUTO, Value::Integer(EXPOSURE_AUTO)))?,
+        (Some(CID_EXPOSURE_AUTO), false, None) => {
+            set_control(dev, CID_EXPOSURE_AUTO, Value::Integer(EXPOSURE_MANUAL))?
+        }
+        (Some(id), auto, _) => set_control(dev, id, Value::Boolean(auto))?,
+    }
+    if !state.auto {
+        set_control(dev, value_id, Value::Integer(state.value as i64))?;
+    }
+    Ok(())
+}
+
+pub fn list_devices() -> Result<Vec<DeviceId>> {
+    let mut found = Vec::new();
+    for node in context::enum_devices() {
+        let path = node.path().to_string_lossy().to_string();
+        let Ok(dev) = Device::with_path(&path) else {
+            continue;
+        };
+        let Ok(caps) = dev.query_caps() else {
+            continue;
+        };
+        if caps.capabilities.contains(Flags::VIDEO_CAPTURE) {
+            let usb = usb_device_dir(&path).and_then(|dir| {
+                let descriptors = fs::read(dir.join("descriptors")).ok();
+                usb_info(&dir, descriptors.as_deref())
+            });
+            found.push(DeviceId {
+                name: caps.card,
+                path,
+                usb,
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// The `index`th video capture device, in enumeration order.
+fn capture_device(index: usize) -> Result<DeviceId> {
+    let mut found = list_devices()?;
+    if found.is_empty() {
+        anyhow::bail!("No camera devices found.");
+    }
+    let count = found.len();
+    if index >= count {
+        anyhow::bail!(
+            "Device {} does not exist. Found {} device(s).",
+            index + 1,
+            count
+        );
+    }
+    Ok(found.swap_remove(index))
+}
+
+/// Pick the first candidate FourCC the device offers at the requested resolution, or
+/// the first one it offers at all at its highest resolution.
+fn find_format(
+    dev: &Device,
+    candidates: &[FourCC],
+    request: &CaptureRequest,
+) -> Result<(FourCC, u32, u32)> {
+    let offered: Vec<FourCC> = dev
+        .enum_formats()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|desc| desc.fourcc)
+        .collect();
+    let offered_candidates: Vec<FourCC> = candidates
+        .iter()
+        .copied()
+        .filter(|c| offered.contains(c))
+        .collect();
+    let fourcc = offered_candidates.first().copied().with_context(|| {
+        format!(
+            "No {} format on this device. Available formats: {}",
+            request.pixel_format,
+            offered
+                .iter()
+                .map(|f| fourcc_name(*f))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+
+    if let Some((rw, rh)) = request.resolution {
+        if let Some(&other) = offered_candidates
+            .iter()
+            .find(|&&c| available_sizes(dev, c, request.resolution).contains(&(rw, rh)))
+        {
+            return Ok((other, rw, rh));
+        }
+    }
+
+    let available = available_sizes(dev, fourcc, request.resolution);
+    let (width, height) = match request.resolution {
+        Some((rw, rh)) if available.contains(&(rw, rh)) => (rw, rh),
+        Some((rw, rh)) => {
+            let avail_str: Vec<String> =
+                available.iter().map(|(w, h)| format!("{w}x{h}")).collect();
+            anyhow::bail!(
+                "No {} format at {rw}x{rh}. Available {} resolutions: {}",
+                fourcc_name(fourcc),
+                fourcc_name(fourcc),
+                avail_str.join(", ")
+            );
+        }
+        None => available
+            .iter()
+            .copied()
+            .max_by_key(|&(w, h)| w as u64 * h as u64)
+            .with_context(|| format!("No frame sizes reported for {}", fourcc_name(fourcc)))?,
+    };
+    Ok((fourcc, width, height))
+}
+
+/// Discrete sizes of a format, plus the largest of a range and `wanted` if the range holds it.
+fn available_sizes(dev: &Device, fourcc: FourCC, wanted: Option<(u32, u32)>) -> Vec<(u32, u32)> {
+    let mut available = Vec::new();
+    for framesize in dev.enum_framesizes(fourcc).unwrap_or_default() {
+        match framesize.size {
+            v4l::framesize::FrameSizeEnum::Discrete(d) => available.push((d.width, d.height)),
+            v4l::framesize::FrameSizeEnum::Stepwise(s) => {
+                if let Some((rw, rh)) = wanted {
+                    if size_range(&s).contains(rw, rh) {
+                        available.push((rw, rh));
+                    }
+                }
+                available.push((s.max_width, s.max_height));
+            }
+        }
+    }
+    available
+}
+
+fn size_range(s: &v4l::framesize::Stepwise) -> SizeRange {
+    SizeRange {
+        min_width: s.min_width,
+        max_width: s.max_width,
+        step_width: s.step_width,
+        min_height: s.min_height,
+        max_height: s.max_height,
+        step_height: s.step_height,
+    }
+}
+
 fn fourcc_name(fourcc: FourCC) -> String {
     fourcc
         .str()
@@ -127,21 +798,86 @@ fn fourcc_name(fourcc: FourCC) -> String {
         .unwrap_or_else(|_| format!("{fourcc:?}"))
 }
 
-fn colorspace_info(cs: Colorspace, tf: TransferFunction, quant: Quantization) -> ColorspaceInfo {
-    let (primaries, matrix) = match cs {
-        Colorspace::Rec709 => ("BT.709".to_string(), "BT.709".to_string()),
-        Colorspace::SMPTE170M => ("SMPTE 170M".to_string(), "BT.601".to_string()),
-        Colorspace::SMPTE240M => ("SMPTE 240M".to_string(), "SMPTE 240M".to_string()),
-        Colorspace::Rec2020 => ("BT.2020".to_string(), "BT.2020".to_string()),
-        Colorspace::SRGB => ("sRGB".to_string(), "sRGB".to_string()),
-        Colorspace::OPRGB => ("opRGB".to_string(), "opRGB".to_string()),
-        Colorspace::JPEG => ("BT.601".to_string(), "BT.601 (JPEG)".to_string()),
-        Colorspace::NTSC => ("NTSC".to_string(), "BT.601".to_string()),
-        Colorspace::EBUTech3212 => ("EBU Tech 3213".to_string(), "BT.601".to_string()),
-        Colorspace::RAW => ("Raw".to_string(), "None".to_string()),
-        Colorspace::DCIP3 => ("DCI-P3".to_string(), "DCI-P3".to_string()),
-        Colorspace::Default => ("Default".to_string(), "Default".to_string()),
-    };
+/// `VIDIOC_G_FMT`, or `VIDIOC_TRY_FMT` for a format and size. The raw structure is
+/// returned because the v4l crate's `Format` drops the Y'CbCr encoding.
+fn query_format(
+    dev: &Device,
+    try_format: Option<(FourCC, u32, u32)>,
+) -> io::Result<v4l2_pix_format> {
+    unsafe {
+        let mut fmt = v4l2_format {
+            type_: Type::VideoCapture as u32,
+            ..mem::zeroed()
+        };
+        let request = match try_format {
+            Some((fourcc, width, height)) => {
+                fmt.fmt.pix.pixelformat = u32::from_le_bytes(fourcc.repr);
+                fmt.fmt.pix.width = width;
+                fmt.fmt.pix.height = height;
+                vidioc::VIDIOC_TRY_FMT
+            }
+            None => vidioc::VIDIOC_G_FMT,
+        };
+        v4l2::ioctl(
+            dev.handle().fd(),
+            request,
+            &mut fmt as *mut v4l2_format as *mut c_void,
+        )?;
+        Ok(fmt.fmt.pix)
+    }
+}
+
+fn pix_colorspace(pix: &v4l2_pix_format) -> ColorspaceInfo {
+    colorspace_info(
+        Colorspace::try_from(pix.colorspace).unwrap_or(Colorspace::Default),
+        TransferFunction::try_from(pix.xfer_func).unwrap_or(TransferFunction::Default),
+        Quantization::try_from(pix.quantization).unwrap_or(Quantization::Default),
+        unsafe { pix.__bindgen_anon_1.ycbcr_enc },
+    )
+}
+
+/// `ycbcr_enc` is a `V4L2_YCBCR_ENC_*` value; the default depends on the colorspace.
+fn colorspace_info(
+    cs: Colorspace,
+    tf: TransferFunction,
+    quant: Quantization,
+    ycbcr_enc: u32,
+) -> ColorspaceInfo {
+    let primaries = match cs {
+        Colorspace::Rec709 => "BT.709",
+        Colorspace::SMPTE170M => "SMPTE 170M",
+        Colorspace::SMPTE240M => "SMPTE 240M",
+        Colorspace::Rec2020 => "BT.2020",
+        Colorspace::SRGB => "sRGB",
+        Colorspace::OPRGB => "opRGB",
+        Colorspace::JPEG => "BT.601",
+        Colorspace::NTSC => "NTSC",
+        Colorspace::EBUTech3212 => "EBU Tech 3213",
+        Colorspace::RAW => "Raw",
+        Colorspace::DCIP3 => "DCI-P3",
+        Colorspace::Default => "Default",
+    }
+    .to_string();
+
+    let matrix = match ycbcr_enc {
+        1 => "BT.601",
+        2 => "BT.709",
+        3 => "BT.601 (xvYCC)",
+        4 => "BT.709 (xvYCC)",
+        5 => "BT.601 (sYCC)",
+        6 => "BT.2020",
+        7 => "BT.2020 (constant luminance)",
+        8 => "SMPTE 240M",
+        _ => match cs {
+            Colorspace::Rec709 | Colorspace::DCIP3 => "BT.709",
+            Colorspace::Rec2020 => "BT.2020",
+            Colorspace::SMPTE240M => "SMPTE 240M",
+            Colorspace::RAW => "None",
+            Colorspace::Default => "Default",
+            _ => "BT.601",
+        },
+    }
+    .to_string();
 
     let transfer = match tf {
         TransferFunction::Rec709 => "BT.709",
@@ -3,21 +3,270 @@ mod linux;
 #[cfg(windows)]
 mod windows;
 
-use crate::cli::MatrixChoice;
+use crate::cli::{DeviceSelector, MatrixChoice};
+use crate::controls::ControlSetting;
+use crate::quirks::{self, Quirk};
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
 
+use serde::{Deserialize, Serialize};
+
+use crate::settle::{self, Settle, SettlePolicy};
+
+#[derive(Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub name: String,
     pub path: String,
+    /// `None` for devices not on USB, or when the OS doesn't reveal the IDs.
+    pub usb: Option<UsbInfo>,
+    /// Known firmware behaviour from the quirks database, looked up by USB identity.
+    pub quirk: Option<Quirk>,
     pub formats: Vec<FormatInfo>,
+    /// Formats from the camera's UVC descriptors; empty where the OS doesn't expose them.
+    pub uvc_formats: Vec<UvcFormat>,
+    pub controls: Vec<ControlInfo>,
+}
+
+/// What tells capture devices apart, read without opening their streams.
+pub struct DeviceId {
+    pub name: String,
+    /// Device node on Linux, symbolic link on Windows.
+    pub path: String,
+    pub usb: Option<UsbInfo>,
 }
 
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.name, self.path)?;
+        if let Some(usb) = &self.usb {
+            write!(f, ", USB {}:{}", usb.vendor_id, usb.product_id)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// What identifies the exact camera model and firmware. IDs are four lower-case hex digits.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UsbInfo {
+    pub vendor_id: String,
+    pub product_id: String,
+    /// Device release number, which vendors bump with firmware updates.
+    pub bcd_device: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    /// Bus number and port path, e.g. "1-2.3" for bus 1, hub port 2, port 3.
+    pub location: Option<String>,
+    /// From the VideoControl interface header, e.g. "1.10".
+    pub uvc_version: Option<String>,
+}
+
+impl fmt::Display for UsbInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.vendor_id, self.product_id)?;
+        if let Some(bcd) = &self.bcd_device {
+            write!(f, ", bcdDevice {bcd}")?;
+        }
+        if let Some(version) = &self.uvc_version {
+            write!(f, ", UVC {version}")?;
+        }
+        if let Some(serial) = &self.serial {
+            write!(f, ", serial {serial}")?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, ", at {location}")?;
+        }
+        let names: Vec<&str> = [&self.manufacturer, &self.product]
+            .into_iter()
+            .flatten()
+            .map(|s| s.as_str())
+            .collect();
+        if !names.is_empty() {
+            write!(f, " ({})", names.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct FormatInfo {
     pub pixel_format: String,
     pub resolution: String,
-    pub frame_rate: String,
+    /// Set when the driver accepts a range of sizes for this format; `resolution` is then
+    /// one of the standard sizes (or range limits) probed from it.
+    pub size_range: Option<SizeRange>,
+    pub frame_rates: FrameRates,
     pub colorspace: ColorspaceInfo,
 }
 
+/// Frame sizes of a V4L2 stepwise or continuous format (continuous has steps of 1).
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeRange {
+    pub min_width: u32,
+    pub max_width: u32,
+    pub step_width: u32,
+    pub min_height: u32,
+    pub max_height: u32,
+    pub step_height: u32,
+}
+
+/// Sizes probed from a [`SizeRange`] in addition to its limits.
+const STANDARD_SIZES: [(u32, u32); 12] = [
+    (320, 240),
+    (640, 360),
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1280, 960),
+    (1600, 1200),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+    (4096, 2160),
+];
+
+impl SizeRange {
+    pub fn contains(&self, width: u32, height: u32) -> bool {
+        let fits = |v: u32, min: u32, max: u32, step: u32| {
+            (min..=max).contains(&v) && (v - min).is_multiple_of(step.max(1))
+        };
+        fits(width, self.min_width, self.max_width, self.step_width)
+            && fits(height, self.min_height, self.max_height, self.step_height)
+    }
+
+    /// The range's smallest and largest sizes plus the standard sizes it accepts,
+    /// smallest first.
+    pub fn probe_sizes(&self) -> Vec<(u32, u32)> {
+        let mut sizes = vec![
+            (self.min_width, self.min_height),
+            (self.max_width, self.max_height),
+        ];
+        sizes.extend(
+            STANDARD_SIZES
+                .iter()
+                .copied()
+                .filter(|&(w, h)| self.contains(w, h)),
+        );
+        sizes.sort_by_key(|&(w, h)| (w as u64 * h as u64, w));
+        sizes.dedup();
+        sizes
+    }
+}
+
+impl fmt::Display for SizeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} to {}x{}, step {}x{}",
+            self.min_width,
+            self.min_height,
+            self.max_width,
+            self.max_height,
+            self.step_width,
+            self.step_height
+        )
+    }
+}
+
+/// An exact ratio, so rates like 30000/1001 survive without rounding.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rational {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl Rational {
+    pub fn value(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+/// The frame rates a format offers.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameRates {
+    /// Specific rates in frames per second.
+    Discrete(Vec<Rational>),
+    /// Any frame interval from `min_interval` to `max_interval` seconds in increments of
+    /// `step` (V4L2 stepwise and continuous intervals; continuous has a step of 1).
+    Stepwise {
+        min_interval: Rational,
+        max_interval: Rational,
+        step: Rational,
+    },
+    Unknown,
+}
+
+impl fmt::Display for FrameRates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRates::Discrete(rates) => {
+                let rates: Vec<String> = rates
+                    .iter()
+                    .map(|r| format!("{:.2} fps", r.value()))
+                    .collect();
+                write!(f, "{}", rates.join(", "))
+            }
+            FrameRates::Stepwise {
+                min_interval,
+                max_interval,
+                step,
+            } => {
+                // The longest interval is the lowest rate
+                write!(
+                    f,
+                    "{:.2}-{:.2} fps (interval {} to {} s, ",
+                    1.0 / max_interval.value(),
+                    1.0 / min_interval.value(),
+                    min_interval,
+                    max_interval
+                )?;
+                if *step == (Rational { num: 1, den: 1 }) {
+                    write!(f, "continuous)")
+                } else {
+                    write!(f, "step {} s)", step)
+                }
+            }
+            FrameRates::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// A format as the firmware declares it in its VideoStreaming descriptors, with the
+/// colour of its Color Matching descriptor next to what the driver made of it.
+#[derive(Serialize, Deserialize)]
+pub struct UvcFormat {
+    /// bInterfaceNumber of the VideoStreaming interface.
+    pub interface: u8,
+    /// bFormatIndex.
+    pub index: u8,
+    /// Under the OS's name for it, e.g. "YUYV" rather than the GUID's "YUY2" on Linux.
+    pub pixel_format: String,
+    pub frame_sizes: Vec<String>,
+    /// False when the format has no Color Matching descriptor and the UVC defaults apply.
+    pub color_matching: bool,
+    pub primaries: String,
+    pub transfer: String,
+    pub matrix: String,
+    /// The matrix the OS reports for this pixel format, if it lists the format.
+    pub reported_matrix: Option<String>,
+    /// The OS reports a different matrix than the descriptor declares.
+    pub mismatch: bool,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct ColorspaceInfo {
     pub primaries: String,
     pub matrix: String,
@@ -25,15 +274,192 @@ pub struct ColorspaceInfo {
     pub range: String,
 }
 
+/// A device control as the driver describes it.
+#[derive(Serialize, Deserialize)]
+pub struct ControlInfo {
+    /// V4L2 control ID, or the `IAMVideoProcAmp`/`IAMCameraControl` property number on Windows.
+    pub id: u32,
+    pub name: String,
+    /// "integer", "boolean", "menu", "integer menu", "button", "string", ...
+    pub kind: String,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: u64,
+    pub default: i64,
+    /// `None` if the control is write-only or its type has no single value.
+    pub current: Option<i64>,
+    /// Menu entries for menu controls.
+    pub menu: Vec<MenuEntry>,
+    /// Driver flags worth knowing when reading the value: "inactive", "read-only", "auto", ...
+    pub flags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MenuEntry {
+    pub value: i64,
+    pub name: String,
+}
+
 pub struct CapturedFrame {
+    /// Identity of the camera captured from, for looking up quirks.
+    pub usb: Option<UsbInfo>,
     pub width: u32,
     pub height: u32,
     pub pixel_format: String,
     pub full_range: bool,
+    /// Colorspace attributes the driver reported on the selected media type.
+    pub colorspace: ColorspaceInfo,
+    /// The newest frame.
     pub data: Vec<u8>,
+    /// Frames captured before `data` in a multi-frame capture, oldest first.
+    pub earlier_frames: Vec<Vec<u8>>,
+}
+
+/// The stream a recording delivers, known before its first frame.
+pub struct StreamFormat {
+    pub usb: Option<UsbInfo>,
+    pub width: u32,
+    pub height: u32,
+    pub full_range: bool,
+    /// Colorspace attributes the driver reported on the selected format.
+    pub colorspace: ColorspaceInfo,
+    /// The rate the stream was set up with, if the driver says.
+    pub frame_rate: Option<Rational>,
+}
+
+/// Receives a recording while it is captured, so it never has to fit in memory.
+pub trait RecordSink {
+    /// Called once, before the first frame.
+    fn start(&mut self, format: &StreamFormat) -> anyhow::Result<()>;
+    /// An NV12 frame and the backend's capture time, relative to the first frame recorded.
+    fn frame(&mut self, data: &[u8], timestamp: Duration) -> anyhow::Result<()>;
+}
+
+/// A frame captured with a matrix override, and what the OS colour converter made of it.
+pub struct OverrideCapture {
+    /// The raw NV12 frame, with the colorspace the driver reported before the override.
+    pub frame: CapturedFrame,
+    /// RGB24 from the OS's own conversion with the driver's attributes.
+    pub converted_reported: Vec<u8>,
+    /// RGB24 from the OS's own conversion with the matrix overridden; `None` if the
+    /// source rejected the override or didn't keep it.
+    pub converted_forced: Option<Vec<u8>>,
+}
+
+/// What to capture from which device.
+pub struct CaptureRequest<'a> {
+    pub device_index: usize,
+    /// Exact resolution, or `None` for the highest available.
+    pub resolution: Option<(u32, u32)>,
+    /// FourCC-style name such as "NV12" or "MJPG".
+    pub pixel_format: &'a str,
+    /// Consecutive frames to keep once the camera has settled.
+    pub frames: usize,
+    pub settle: SettlePolicy,
+    /// Controls to pin for the capture; the previous values are restored afterwards.
+    pub controls: &'a [ControlSetting],
+}
+
+/// Feeds a backend's frames through the settle policy and keeps the requested number
+/// once the camera has settled.
+struct FrameCollector {
+    settle: Settle,
+    wanted: usize,
+    /// Frames are NV12 of this size and can be measured; `None` for compressed formats.
+    nv12_size: Option<(u32, u32)>,
+    kept: Vec<Vec<u8>>,
+}
+
+impl FrameCollector {
+    fn new(request: &CaptureRequest, nv12_size: Option<(u32, u32)>) -> Self {
+        FrameCollector {
+            settle: Settle::new(request.settle),
+            wanted: request.frames,
+            nv12_size,
+            kept: Vec::with_capacity(request.frames),
+        }
+    }
+
+    /// Offer the next frame. Returns true once enough frames have been kept.
+    fn push(&mut self, frame: Vec<u8>) -> bool {
+        let means = self
+            .nv12_size
+            .and_then(|(w, h)| settle::nv12_means(&frame, w, h));
+        let was_settled = self.settle.is_settled();
+        if self.settle.observe(means) {
+            if !was_settled {
+                println!("{}", self.settle.summary());
+            }
+            self.kept.push(frame);
+        }
+        self.kept.len() >= self.wanted
+    }
+
+    /// The newest frame and the ones before it, oldest first.
+    fn finish(mut self) -> Option<(Vec<u8>, Vec<Vec<u8>>)> {
+        let newest = self.kept.pop()?;
+        Some((newest, self.kept))
+    }
+}
+
+/// Feeds a backend's frames through the settle policy, then to a sink until the
+/// recording time is up.
+struct Recording<'a> {
+    settle: Settle,
+    size: (u32, u32),
+    duration: Duration,
+    /// Backend timestamp and wall-clock time of the first recorded frame. The stop is
+    /// timed by the wall clock, in case the backend's timestamps are unusable.
+    first: Option<(Duration, Instant)>,
+    sink: &'a mut dyn RecordSink,
+}
+
+impl<'a> Recording<'a> {
+    fn start(
+        request: &CaptureRequest,
+        duration: Duration,
+        format: &StreamFormat,
+        sink: &'a mut dyn RecordSink,
+    ) -> anyhow::Result<Self> {
+        sink.start(format)?;
+        Ok(Recording {
+            settle: Settle::new(request.settle),
+            size: (format.width, format.height),
+            duration,
+            first: None,
+            sink,
+        })
+    }
+
+    /// Offer the next NV12 frame. Returns true once the recording time is up.
+    fn push(&mut self, frame: &[u8], timestamp: Duration) -> anyhow::Result<bool> {
+        if !self.settle.is_settled() {
+            let (w, h) = self.size;
+            if !self.settle.observe(settle::nv12_means(frame, w, h)) {
+                return Ok(false);
+            }
+            println!("{}", self.settle.summary());
+        }
+        let (first, started) = *self.first.get_or_insert((timestamp, Instant::now()));
+        self.sink.frame(frame, timestamp.saturating_sub(first))?;
+        Ok(started.elapsed() >= self.duration)
+    }
 }
 
 pub fn enumerate_devices() -> anyhow::Result<Vec<DeviceInfo>> {
+    let mut devices = platform_devices()?;
+    let database = quirks::Database::load();
+    for device in &mut devices {
+        device.quirk = device
+            .usb
+            .as_ref()
+            .and_then(|usb| database.lookup(usb))
+            .cloned();
+    }
+    Ok(devices)
+}
+
+fn platform_devices() -> anyhow::Result<Vec<DeviceInfo>> {
     #[cfg(windows)]
     {
         windows::enumerate_devices()
@@ -48,22 +474,194 @@ pub fn enumerate_devices() -> anyhow::Result<Vec<DeviceInfo>> {
     }
 }
 
-pub fn capture_frame(
-    device_index: usize,
-    resolution: Option<(u32, u32)>,
-) -> anyhow::Result<CapturedFrame> {
+/// Capture devices in the order device numbers refer to.
+pub fn list_devices() -> anyhow::Result<Vec<DeviceId>> {
+    #[cfg(windows)]
+    {
+        windows::list_devices()
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::list_devices()
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        anyhow::bail!("Unsupported platform")
+    }
+}
+
+/// The 0-based index of the one device a selector names, and the device.
+pub fn select_device(selector: &DeviceSelector) -> anyhow::Result<(usize, DeviceId)> {
+    let mut devices = list_devices()?;
+    if devices.is_empty() {
+        anyhow::bail!("No camera devices found.");
+    }
+    let query = match selector {
+        DeviceSelector::Index(index) if *index < devices.len() => {
+            return Ok((*index, devices.swap_remove(*index)));
+        }
+        DeviceSelector::Index(index) => anyhow::bail!(
+            "Device {} does not exist. Found {} device(s).",
+            index + 1,
+            devices.len()
+        ),
+        DeviceSelector::Query(query) => query,
+    };
+
+    let list = |indices: &mut dyn Iterator<Item = usize>| {
+        indices
+            .map(|i| format!("  {}: {}", i + 1, devices[i]))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match matching_devices(query, &devices)[..] {
+        [index] => Ok((index, devices.swap_remove(index))),
+        [] => anyhow::bail!(
+            "No device matches '{}'. Devices:\n{}",
+            query,
+            list(&mut (0..devices.len()))
+        ),
+        ref candidates => anyhow::bail!(
+            "'{}' matches {} devices:\n{}\nUse a device number, path or more of the name to pick one.",
+            query,
+            candidates.len(),
+            list(&mut candidates.iter().copied())
+        ),
+    }
+}
+
+/// Indices of the devices a `--device` query names. A path (or one that resolves to the
+/// same device node, such as a /dev/v4l/by-id link) or USB ID must match exactly; other
+/// text matches names case-insensitively, preferring a whole name to a substring.
+fn matching_devices(query: &str, devices: &[DeviceId]) -> Vec<usize> {
+    let positions = |matches: &dyn Fn(&DeviceId) -> bool| {
+        (0..devices.len())
+            .filter(|&i| matches(&devices[i]))
+            .collect::<Vec<_>>()
+    };
+
+    let target = fs::canonicalize(query).ok();
+    let by_path = positions(&|d| {
+        d.path.eq_ignore_ascii_case(query)
+            || target.is_some() && fs::canonicalize(&d.path).ok() == target
+    });
+    if !by_path.is_empty() {
+        return by_path;
+    }
+
+    if let Some((vendor, product, revision)) = parse_usb_id(query) {
+        return positions(&|d| {
+            d.usb.as_ref().is_some_and(|usb| {
+                usb.vendor_id.eq_ignore_ascii_case(vendor)
+                    && usb.product_id.eq_ignore_ascii_case(product)
+                    && revision.is_none_or(|rev| {
+                        usb.bcd_device
+                            .as_ref()
+                            .is_some_and(|bcd| bcd.eq_ignore_ascii_case(rev))
+                    })
+            })
+        });
+    }
+
+    let query = query.to_lowercase();
+    let whole_name = positions(&|d| d.name.to_lowercase() == query);
+    if !whole_name.is_empty() {
+        return whole_name;
+    }
+    positions(&|d| d.name.to_lowercase().contains(&query))
+}
+
+/// "VID:PID" or "VID:PID:bcdDevice", each four hex digits.
+fn parse_usb_id(text: &str) -> Option<(&str, &str, Option<&str>)> {
+    let parts: Vec<&str> = text.split(':').collect();
+    let hex4 = |s: &str| s.len() == 4 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if !parts.iter().all(|p| hex4(p)) {
+        return None;
+    }
+    match parts[..] {
+        [vendor, product] => Some((vendor, product, None)),
+        [vendor, product, revision] => Some((vendor, product, Some(revision))),
+        _ => None,
+    }
+}
+
+pub fn capture_frame(request: &CaptureRequest) -> anyhow::Result<CapturedFrame> {
+    #[cfg(windows)]
+    {
+        windows::capture_frame(request)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::capture_frame(request)
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let _ = request;
+        anyhow::bail!("Unsupported platform")
+    }
+}
+
+/// Record NV12 frames for `duration` once the camera has settled, handing them to `sink`
+/// as they arrive. `request.frames` is not used.
+pub fn record(
+    request: &CaptureRequest,
+    duration: Duration,
+    sink: &mut dyn RecordSink,
+) -> anyhow::Result<()> {
+    #[cfg(windows)]
+    {
+        windows::record(request, duration, sink)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::record(request, duration, sink)
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let _ = (request, duration, sink);
+        anyhow::bail!("Unsupported platform")
+    }
+}
+
+/// Capture a frame the way an app asking for RGB gets it: the camera delivers
+/// `request.pixel_format` and the OS converts it. Returns top-down RGB24 at the
+/// requested resolution, which must be given.
+pub fn capture_os_rgb(request: &CaptureRequest) -> anyhow::Result<Vec<u8>> {
+    #[cfg(windows)]
+    {
+        windows::capture_os_rgb(request)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let _ = request;
+        anyhow::bail!(
+            "--compare-os is not yet supported on Linux: V4L2 only delivers the camera's own formats, and the RGB conversion apps get comes from libv4l, which this tool doesn't use"
+        )
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let _ = request;
+        anyhow::bail!("Unsupported platform")
+    }
+}
+
+/// Override the matrix, capture, and convert the frame with the OS's own converter.
+pub fn force_matrix_capture(
+    request: &CaptureRequest,
+    matrix: MatrixChoice,
+) -> anyhow::Result<OverrideCapture> {
     #[cfg(windows)]
     {
-        windows::capture_frame(device_index, resolution)
+        windows::force_matrix_capture(request, matrix)
     }
     #[cfg(target_os = "linux")]
     {
-        let _ = (device_index, resolution);
-        anyhow::bail!("--capture-test is not yet supported on Linux")
+        let _ = (request, matrix);
+        anyhow::bail!("force is not yet supported on Linux")
     }
     #[cfg(not(any(windows, target_os = "linux")))]
     {
-        let _ = (device_index, resolution);
+        let _ = (request, matrix);
         anyhow::bail!("Unsupported platform")
     }
 }
@@ -76,7 +674,7 @@ pub fn force_matrix(device_index: usize, matrix: MatrixChoice) -> anyhow::Result
     #[cfg(target_os = "linux")]
     {
         let _ = (device_index, matrix);
-        anyhow::bail!("--force-matrix is not yet supported on Linux")
+        anyhow::bail!("force is not yet supported on Linux")
     }
     #[cfg(not(any(windows, target_os = "linux")))]
     {
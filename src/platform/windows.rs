@@ -1,9 +1,30 @@
+use std::time::Duration;
+
 use anyhow::{Context, Result};
+use windows::core::Interface;
+use windows::Win32::Devices::DeviceAndDriverInstallation::{
+    CM_Get_DevNode_Registry_PropertyW, CM_Locate_DevNodeW, CM_DRP_HARDWAREID,
+    CM_LOCATE_DEVNODE_NORMAL, CR_SUCCESS,
+};
+use windows::Win32::Media::DirectShow::{
+    CameraControlProperty, CameraControl_Exposure, CameraControl_Flags_Auto,
+    CameraControl_Flags_Manual, CameraControl_Focus, CameraControl_Iris, CameraControl_Pan,
+    CameraControl_Roll, CameraControl_Tilt, CameraControl_Zoom, IAMCameraControl, IAMVideoProcAmp,
+    VideoProcAmpProperty, VideoProcAmp_BacklightCompensation, VideoProcAmp_Brightness,
+    VideoProcAmp_ColorEnable, VideoProcAmp_Contrast, VideoProcAmp_Flags_Auto,
+    VideoProcAmp_Flags_Manual, VideoProcAmp_Gain, VideoProcAmp_Gamma, VideoProcAmp_Hue,
+    VideoProcAmp_Saturation, VideoProcAmp_Sharpness, VideoProcAmp_WhiteBalance,
+};
 use windows::Win32::Media::MediaFoundation::*;
 use windows::Win32::System::Com::*;
 
-use super::{CapturedFrame, ColorspaceInfo, DeviceInfo, FormatInfo};
+use super::{
+    CaptureRequest, CapturedFrame, ColorspaceInfo, ControlInfo, DeviceId, DeviceInfo, FormatInfo,
+    FrameCollector, FrameRates, OverrideCapture, Rational, RecordSink, Recording, StreamFormat,
+    UsbInfo,
+};
 use crate::cli::MatrixChoice;
+use crate::controls::{self, CameraControl, ControlState};
 
 const FIRST_VIDEO_STREAM: u32 = 0xFFFFFFFC; // MF_SOURCE_READER_FIRST_VIDEO_STREAM
 
@@ -32,14 +53,39 @@ pub fn enumerate_devices() -> Result<Vec<DeviceInfo>> {
     with_mf(|| unsafe { enumerate_devices_inner() })
 }
 
-pub fn capture_frame(device_index: usize, resolution: Option<(u32, u32)>) -> Result<CapturedFrame> {
-    with_mf(|| unsafe { capture_frame_inner(device_index, resolution) })
+pub fn list_devices() -> Result<Vec<DeviceId>> {
+    with_mf(|| unsafe { list_devices_inner() })
+}
+
+pub fn capture_frame(request: &CaptureRequest) -> Result<CapturedFrame> {
+    with_mf(|| unsafe { capture_frame_inner(request) })
+}
+
+pub fn record(
+    request: &CaptureRequest,
+    duration: Duration,
+    sink: &mut dyn RecordSink,
+) -> Result<()> {
+    with_mf(|| unsafe { record_inner(request, duration, sink) })
 }
 
 pub fn force_matrix(device_index: usize, matrix: MatrixChoice) -> Result<()> {
     with_mf(|| unsafe { force_matrix_inner(device_index, matrix) })
 }
 
+pub fn capture_os_rgb(request: &CaptureRequest) -> Result<Vec<u8>> {
+    with_mf(|| unsafe {
+        os_rgb_inner(request, None)?.context("The source reader returned no RGB frame")
+    })
+}
+
+pub fn force_matrix_capture(
+    request: &CaptureRequest,
+    matrix: MatrixChoice,
+) -> Result<OverrideCapture> {
+    with_mf(|| unsafe { force_matrix_capture_inner(request, matrix) })
+}
+
 // ---------------------------------------------------------------------------
 // Device activation helpers
 // ---------------------------------------------------------------------------
@@ -75,8 +121,8 @@ unsafe fn enum_activates() -> Result<Vec<IMFActivate>> {
     Ok(activates)
 }
 
-/// Activate a specific device by 0-based index. Returns (source, friendly_name).
-unsafe fn activate_device_by_index(index: usize) -> Result<(IMFMediaSource, String)> {
+/// Activate a specific device by 0-based index. Returns (source, friendly_name, symbolic_link).
+unsafe fn activate_device_by_index(index: usize) -> Result<(IMFMediaSource, String, String)> {
     let activates = enum_activates()?;
 
     if activates.is_empty() {
@@ -94,12 +140,36 @@ unsafe fn activate_device_by_index(index: usize) -> Result<(IMFMediaSource, Stri
 
     let name = get_string_attribute(activate, &MF_DEVSOURCE_ATTRIBUTE_FRIENDLY_NAME)
         .unwrap_or_else(|_| "Unknown".to_string());
+    let link = get_string_attribute(
+        activate,
+        &MF_DEVSOURCE_ATTRIBUTE_SOURCE_TYPE_VIDCAP_SYMBOLIC_LINK,
+    )
+    .unwrap_or_default();
 
     let source: IMFMediaSource = activate
         .ActivateObject()
         .context("Failed to activate media source")?;
 
-    Ok((source, name))
+    Ok((source, name, link))
+}
+
+unsafe fn list_devices_inner() -> Result<Vec<DeviceId>> {
+    let mut devices = Vec::new();
+    for activate in &enum_activates()? {
+        let name = get_string_attribute(activate, &MF_DEVSOURCE_ATTRIBUTE_FRIENDLY_NAME)
+            .unwrap_or_else(|_| "Unknown".to_string());
+        let path = get_string_attribute(
+            activate,
+            &MF_DEVSOURCE_ATTRIBUTE_SOURCE_TYPE_VIDCAP_SYMBOLIC_LINK,
+        )
+        .unwrap_or_default();
+        devices.push(DeviceId {
+            usb: usb_info_from_symlink(&path),
+            name,
+            path,
+        });
+    }
+    Ok(devices)
 }
 
 // ---------------------------------------------------------------------------
@@ -177,24 +247,229 @@ unsafe fn read_device(activate: &IMFActivate) -> Result<DeviceInfo> {
         }
     }
 
+    let controls = read_controls(&source);
+
     let _ = source.Shutdown();
 
     Ok(DeviceInfo {
+        usb: usb_info_from_symlink(&path),
+        quirk: None,
         name,
         path,
         formats,
+        // Raw configuration descriptors need a USB filter driver on Windows
+        uvc_formats: Vec::new(),
+        controls,
+    })
+}
+
+/// USB identity parsed from a symbolic link such as
+/// `\\?\usb#vid_046d&pid_085e&mi_00#7&2f5c1d5&0&0000#{e5323777-...}\global`.
+///
+/// The segment after the IDs is the instance ID. It is the serial number only for a
+/// single-interface device with a serial; otherwise Windows generates one containing '&'.
+/// Links rarely carry the revision, so it comes from the device's hardware IDs.
+fn usb_info_from_symlink(link: &str) -> Option<UsbInfo> {
+    let mut segments = link.split('#');
+    if !segments.next()?.to_lowercase().ends_with("usb") {
+        return None;
+    }
+    let ids = segments.next()?.to_lowercase();
+    let instance = segments.next().unwrap_or_default();
+
+    let field = |prefix: &str| id_field(&ids, prefix);
+    let composite = field("mi_").is_some();
+    let serial = (!composite && !instance.is_empty() && !instance.contains('&'))
+        .then(|| instance.to_string());
+
+    Some(UsbInfo {
+        vendor_id: field("vid_")?,
+        product_id: field("pid_")?,
+        bcd_device: field("rev_").or_else(|| usb_revision(&format!("usb\\{ids}\\{instance}"))),
+        manufacturer: None,
+        product: None,
+        serial,
+        location: None,
+        uvc_version: None,
     })
 }
 
+/// The value after `prefix` in an `&`-separated ID such as `vid_046d&pid_085e&rev_0016`.
+fn id_field(ids: &str, prefix: &str) -> Option<String> {
+    ids.split('&')
+        .find_map(|part| part.strip_prefix(prefix))
+        .map(|v| v.to_string())
+}
+
+/// bcdDevice from the `REV_xxxx` part of a device instance's hardware IDs, such as
+/// `USB\VID_046D&PID_085E&REV_0016&MI_00`.
+fn usb_revision(instance_id: &str) -> Option<String> {
+    let wide: Vec<u16> = instance_id.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        let mut devinst = 0u32;
+        if CM_Locate_DevNodeW(
+            &mut devinst,
+            windows::core::PCWSTR(wide.as_ptr()),
+            CM_LOCATE_DEVNODE_NORMAL,
+        ) != CR_SUCCESS
+        {
+            return None;
+        }
+        // The first call only reports the size of the REG_MULTI_SZ list
+        let mut len = 0u32;
+        let _ =
+            CM_Get_DevNode_Registry_PropertyW(devinst, CM_DRP_HARDWAREID, None, None, &mut len, 0);
+        if len == 0 {
+            return None;
+        }
+        let mut buffer = vec![0u16; (len as usize).div_ceil(2)];
+        if CM_Get_DevNode_Registry_PropertyW(
+            devinst,
+            CM_DRP_HARDWAREID,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            &mut len,
+            0,
+        ) != CR_SUCCESS
+        {
+            return None;
+        }
+        String::from_utf16_lossy(&buffer)
+            .split('\0')
+            .find_map(|id| id_field(&id.to_lowercase(), "rev_"))
+    }
+}
+
+const PROC_AMP_PROPERTIES: [(VideoProcAmpProperty, &str); 10] = [
+    (VideoProcAmp_Brightness, "Brightness"),
+    (VideoProcAmp_Contrast, "Contrast"),
+    (VideoProcAmp_Hue, "Hue"),
+    (VideoProcAmp_Saturation, "Saturation"),
+    (VideoProcAmp_Sharpness, "Sharpness"),
+    (VideoProcAmp_Gamma, "Gamma"),
+    (VideoProcAmp_ColorEnable, "Color Enable"),
+    (VideoProcAmp_WhiteBalance, "White Balance"),
+    (VideoProcAmp_BacklightCompensation, "Backlight Compensation"),
+    (VideoProcAmp_Gain, "Gain"),
+];
+
+const CAMERA_PROPERTIES: [(CameraControlProperty, &str); 7] = [
+    (CameraControl_Pan, "Pan"),
+    (CameraControl_Tilt, "Tilt"),
+    (CameraControl_Roll, "Roll"),
+    (CameraControl_Zoom, "Zoom"),
+    (CameraControl_Exposure, "Exposure"),
+    (CameraControl_Iris, "Iris"),
+    (CameraControl_Focus, "Focus"),
+];
+
+/// The `IAMVideoProcAmp` and `IAMCameraControl` properties the driver implements.
+unsafe fn read_controls(source: &IMFMediaSource) -> Vec<ControlInfo> {
+    let mut controls = Vec::new();
+    // GetRange fails for properties the driver doesn't implement
+    if let Ok(proc_amp) = source.cast::<IAMVideoProcAmp>() {
+        for (property, name) in PROC_AMP_PROPERTIES {
+            let mut range = [0i32; 5];
+            let [min, max, step, default, caps] = &mut range;
+            if proc_amp
+                .GetRange(property.0, min, max, step, default, caps)
+                .is_err()
+            {
+                continue;
+            }
+            let (mut value, mut flags) = (0, 0);
+            let current = proc_amp
+                .Get(property.0, &mut value, &mut flags)
+                .ok()
+                .map(|()| (value, flags));
+            controls.push(directshow_control(
+                property.0,
+                name,
+                range,
+                current,
+                VideoProcAmp_Flags_Auto.0,
+            ));
+        }
+    }
+    if let Ok(camera) = source.cast::<IAMCameraControl>() {
+        for (property, name) in CAMERA_PROPERTIES {
+            let mut range = [0i32; 5];
+            let [min, max, step, default, caps] = &mut range;
+            if camera
+                .GetRange(property.0, min, max, step, default, caps)
+                .is_err()
+            {
+                continue;
+            }
+            let (mut value, mut flags) = (0, 0);
+            let current = camera
+                .Get(property.0, &mut value, &mut flags)
+                .ok()
+                .map(|()| (value, flags));
+            controls.push(directshow_control(
+                property.0,
+                name,
+                range,
+                current,
+                CameraControl_Flags_Auto.0,
+            ));
+        }
+    }
+    controls
+}
+
+/// Build a [`ControlInfo`] from `GetRange` output (min, max, step, default, caps flags)
+/// and the current `Get` value and flags.
+fn directshow_control(
+    property: i32,
+    name: &str,
+    [min, max, step, default, caps]: [i32; 5],
+    current: Option<(i32, i32)>,
+    auto_flag: i32,
+) -> ControlInfo {
+    let mut flags = Vec::new();
+    if caps & auto_flag != 0 {
+        flags.push("auto-capable".to_string());
+    }
+    if current.is_some_and(|(_, f)| f & auto_flag != 0) {
+        flags.push("auto".to_string());
+    }
+    ControlInfo {
+        id: property as u32,
+        name: name.to_string(),
+        kind: "integer".to_string(),
+        minimum: min as i64,
+        maximum: max as i64,
+        step: step.max(0) as u64,
+        default: default as i64,
+        current: current.map(|(v, _)| v as i64),
+        menu: Vec::new(),
+        flags,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Capture
 // ---------------------------------------------------------------------------
 
-/// Find an NV12 media type matching the requested resolution, or the highest-res if none specified.
-unsafe fn find_nv12_type(
+/// Map a pixel format name to its Media Foundation subtype GUID.
+fn subtype_guid(name: &str) -> Result<windows::core::GUID> {
+    match name {
+        "NV12" => Ok(MFVideoFormat_NV12),
+        "MJPG" => Ok(MFVideoFormat_MJPG),
+        other => anyhow::bail!("Capturing {other} is not supported"),
+    }
+}
+
+/// Find a media type of the given subtype matching the requested resolution,
+/// or the highest-res if none specified.
+unsafe fn find_media_type(
     source: &IMFMediaSource,
+    subtype_name: &str,
     requested: Option<(u32, u32)>,
 ) -> Result<(IMFMediaType, u32, u32)> {
+    let wanted = subtype_guid(subtype_name)?;
+
     let pd = source
         .CreatePresentationDescriptor()
         .context("Failed to create presentation descriptor")?;
@@ -231,7 +506,7 @@ unsafe fn find_nv12_type(
             let Ok(subtype) = media_type.GetGUID(&MF_MT_SUBTYPE) else {
                 continue;
             };
-            if subtype != MFVideoFormat_NV12 {
+            if subtype != wanted {
                 continue;
             }
 
@@ -264,48 +539,191 @@ unsafe fn find_nv12_type(
     if let Some((rw, rh)) = requested {
         let avail_str: Vec<String> = available.iter().map(|(w, h)| format!("{w}x{h}")).collect();
         anyhow::bail!(
-            "No NV12 format at {rw}x{rh}. Available NV12 resolutions: {}",
+            "No {subtype_name} format at {rw}x{rh}. Available {subtype_name} resolutions: {}",
             avail_str.join(", ")
         );
     }
 
-    best.context("No NV12 media type found on this device")
+    best.with_context(|| format!("No {subtype_name} media type found on this device"))
+}
+
+/// A device set up to stream the requested format through a source reader.
+struct OpenStream {
+    source: IMFMediaSource,
+    reader: IMFSourceReader,
+    format: StreamFormat,
 }
 
-unsafe fn capture_frame_inner(
-    device_index: usize,
-    resolution: Option<(u32, u32)>,
-) -> Result<CapturedFrame> {
-    let (source, name) = activate_device_by_index(device_index)?;
+unsafe fn open_stream(request: &CaptureRequest) -> Result<OpenStream> {
+    let CaptureRequest {
+        device_index,
+        resolution,
+        pixel_format,
+        ..
+    } = *request;
+    let (source, name, link) = activate_device_by_index(device_index)?;
     println!("Capturing from device {}: {}", device_index + 1, name);
 
-    let (nv12_type, width, height) = find_nv12_type(&source, resolution)?;
-    println!("Selected NV12 {}x{}", width, height);
+    let (media_type, width, height) = find_media_type(&source, pixel_format, resolution)?;
+    println!("Selected {} {}x{}", pixel_format, width, height);
+
+    let FormatInfo {
+        colorspace,
+        frame_rates,
+        ..
+    } = read_format(&media_type)?;
+    let frame_rate = match frame_rates {
+        FrameRates::Discrete(rates) => rates.first().copied(),
+        _ => None,
+    };
 
     // Read nominal range from the media type
     let full_range = matches!(
-        nv12_type.GetUINT32(&MF_MT_VIDEO_NOMINAL_RANGE),
+        media_type.GetUINT32(&MF_MT_VIDEO_NOMINAL_RANGE),
         Ok(v) if v == MFNominalRange_0_255.0 as u32
     );
-    let range_label = if full_range {
-        "Full (0-255)"
-    } else {
-        "Limited (16-235)"
-    };
-    println!("Nominal range: {}", range_label);
+    if pixel_format != "MJPG" {
+        let range_label = if full_range {
+            "Full (0-255)"
+        } else {
+            "Limited (16-235)"
+        };
+        println!("Nominal range: {}", range_label);
+    }
 
     let reader = MFCreateSourceReaderFromMediaSource(&source, None)
         .context("Failed to create source reader")?;
 
     reader
-        .SetCurrentMediaType(FIRST_VIDEO_STREAM, None, &nv12_type)
+        .SetCurrentMediaType(FIRST_VIDEO_STREAM, None, &media_type)
         .context("Failed to set media type on reader")?;
 
-    // Read several frames to let auto-exposure settle, keep the last one
-    let mut last_sample: Option<IMFSample> = None;
-    let frames_to_skip = 5;
+    Ok(OpenStream {
+        source,
+        reader,
+        format: StreamFormat {
+            usb: usb_info_from_symlink(&link),
+            width,
+            height,
+            full_range,
+            colorspace,
+            frame_rate,
+        },
+    })
+}
+
+unsafe fn capture_frame_inner(request: &CaptureRequest) -> Result<CapturedFrame> {
+    let OpenStream {
+        source,
+        reader,
+        format,
+    } = open_stream(request)?;
+
+    let amps = ControlInterfaces {
+        camera: source.cast().ok(),
+        proc_amp: source.cast().ok(),
+    };
+    let result = controls::with_controls(
+        request.controls,
+        |control| amps.read(control),
+        |control, state| amps.write(control, state),
+        || read_frames(&reader, request, format.width, format.height),
+    );
+
+    let _ = source.Shutdown();
+    let (data, earlier_frames) = result?;
+
+    Ok(CapturedFrame {
+        usb: format.usb,
+        width: format.width,
+        height: format.height,
+        pixel_format: request.pixel_format.to_string(),
+        full_range: format.full_range,
+        colorspace: format.colorspace,
+        data,
+        earlier_frames,
+    })
+}
+
+unsafe fn record_inner(
+    request: &CaptureRequest,
+    duration: Duration,
+    sink: &mut dyn RecordSink,
+) -> Result<()> {
+    let OpenStream {
+        source,
+        reader,
+        format,
+    } = open_stream(request)?;
+
+    let amps = ControlInterfaces {
+        camera: source.cast().ok(),
+        proc_amp: source.cast().ok(),
+    };
+    let result = Recording::start(request, duration, &format, sink).and_then(|mut recording| {
+        controls::with_controls(
+            request.controls,
+            |control| amps.read(control),
+            |control, state| amps.write(control, state),
+            || record_frames(&reader, &mut recording),
+        )
+    });
+
+    let _ = source.Shutdown();
+    result
+}
 
-    for i in 0..frames_to_skip {
+/// Read samples into a recording until it is complete.
+unsafe fn record_frames(reader: &IMFSourceReader, recording: &mut Recording) -> Result<()> {
+    let max_empty_reads = 30;
+    let mut empty_reads = 0;
+    loop {
+        let mut flags: u32 = 0;
+        // In 100 ns units
+        let mut timestamp: i64 = 0;
+        let mut sample: Option<IMFSample> = None;
+
+        reader
+            .ReadSample(
+                FIRST_VIDEO_STREAM,
+                0,
+                None,
+                Some(&mut flags),
+                Some(&mut timestamp),
+                Some(&mut sample),
+            )
+            .context("ReadSample failed")?;
+
+        let Some(s) = sample else {
+            empty_reads += 1;
+            if empty_reads == max_empty_reads {
+                anyhow::bail!("No sample received from camera");
+            }
+            continue;
+        };
+        empty_reads = 0;
+        let timestamp = Duration::from_nanos(timestamp.max(0) as u64 * 100);
+        if recording.push(&sample_bytes(&s)?, timestamp)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Let auto-exposure settle per the request's policy, then keep the requested frames.
+unsafe fn read_frames(
+    reader: &IMFSourceReader,
+    request: &CaptureRequest,
+    width: u32,
+    height: u32,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+    let nv12 = request.pixel_format == "NV12";
+    let mut collector = FrameCollector::new(request, nv12.then_some((width, height)));
+    // Reads can return no sample (stream gaps); give up after this many in a row
+    let max_empty_reads = 30;
+    let mut empty_reads = 0;
+    let mut i = 0;
+
+    loop {
         let mut flags: u32 = 0;
         let mut sample: Option<IMFSample> = None;
 
@@ -319,14 +737,100 @@ unsafe fn capture_frame_inner(
                 Some(&mut sample),
             )
             .with_context(|| format!("ReadSample failed on frame {}", i + 1))?;
+        i += 1;
+
+        let Some(s) = sample else {
+            empty_reads += 1;
+            if empty_reads == max_empty_reads {
+                anyhow::bail!("No sample received from camera");
+            }
+            continue;
+        };
+        empty_reads = 0;
+        if collector.push(sample_bytes(&s)?) {
+            break;
+        }
+    }
+
+    collector.finish().context("No sample received from camera")
+}
+
+/// The DirectShow control interfaces a UVC media source exposes; either may be missing.
+struct ControlInterfaces {
+    camera: Option<IAMCameraControl>,
+    proc_amp: Option<IAMVideoProcAmp>,
+}
+
+impl ControlInterfaces {
+    fn read(&self, control: CameraControl) -> Result<Option<ControlState>> {
+        let mut value = 0i32;
+        let mut flags = 0i32;
+        // A failed Get means the driver doesn't implement the property
+        let (ok, auto_flag) = unsafe {
+            match (control, &self.camera, &self.proc_amp) {
+                (CameraControl::Exposure, Some(camera), _) => (
+                    camera
+                        .Get(CameraControl_Exposure.0, &mut value, &mut flags)
+                        .is_ok(),
+                    CameraControl_Flags_Auto.0,
+                ),
+                (CameraControl::Exposure, None, _) => return Ok(None),
+                (_, _, Some(proc_amp)) => (
+                    proc_amp
+                        .Get(proc_amp_property(control), &mut value, &mut flags)
+                        .is_ok(),
+                    VideoProcAmp_Flags_Auto.0,
+                ),
+                (_, _, None) => return Ok(None),
+            }
+        };
+        Ok(ok.then_some(ControlState {
+            auto: flags & auto_flag != 0,
+            value,
+            mode: None,
+        }))
+    }
 
-        if let Some(s) = sample {
-            last_sample = Some(s);
+    fn write(&self, control: CameraControl, state: ControlState) -> Result<()> {
+        unsafe {
+            match (control, &self.camera, &self.proc_amp) {
+                (CameraControl::Exposure, Some(camera), _) => {
+                    let flags = if state.auto {
+                        CameraControl_Flags_Auto
+                    } else {
+                        CameraControl_Flags_Manual
+                    };
+                    camera.Set(CameraControl_Exposure.0, state.value, flags.0)?;
+                }
+                (CameraControl::Exposure, None, _) => {
+                    anyhow::bail!("the device has no IAMCameraControl interface")
+                }
+                (_, _, Some(proc_amp)) => {
+                    let flags = if state.auto {
+                        VideoProcAmp_Flags_Auto
+                    } else {
+                        VideoProcAmp_Flags_Manual
+                    };
+                    proc_amp.Set(proc_amp_property(control), state.value, flags.0)?;
+                }
+                (_, _, None) => anyhow::bail!("the device has no IAMVideoProcAmp interface"),
+            }
         }
+        Ok(())
     }
+}
 
-    let sample = last_sample.context("No sample received from camera")?;
+fn proc_amp_property(control: CameraControl) -> i32 {
+    match control {
+        CameraControl::WhiteBalance => VideoProcAmp_WhiteBalance.0,
+        CameraControl::Gain => VideoProcAmp_Gain.0,
+        CameraControl::BacklightCompensation => VideoProcAmp_BacklightCompensation.0,
+        CameraControl::Exposure => unreachable!("exposure is an IAMCameraControl property"),
+    }
+}
 
+/// Copy a sample's pixels out of its (possibly multi-buffer) storage.
+unsafe fn sample_bytes(sample: &IMFSample) -> Result<Vec<u8>> {
     let buffer = sample
         .ConvertToContiguousBuffer()
         .context("Failed to convert sample to contiguous buffer")?;
@@ -341,16 +845,7 @@ unsafe fn capture_frame_inner(
     let data = std::slice::from_raw_parts(buf_ptr, cur_len as usize).to_vec();
 
     buffer.Unlock().context("Failed to unlock buffer")?;
-
-    let _ = source.Shutdown();
-
-    Ok(CapturedFrame {
-        width,
-        height,
-        pixel_format: "NV12".to_string(),
-        full_range,
-        data,
-    })
+    Ok(data)
 }
 
 // ---------------------------------------------------------------------------
@@ -358,7 +853,7 @@ unsafe fn capture_frame_inner(
 // ---------------------------------------------------------------------------
 
 unsafe fn force_matrix_inner(device_index: usize, matrix: MatrixChoice) -> Result<()> {
-    let (source, name) = activate_device_by_index(device_index)?;
+    let (source, name, _) = activate_device_by_index(device_index)?;
     println!("Device {}: {}", device_index + 1, name);
 
     let reader = MFCreateSourceReaderFromMediaSource(&source, None)
@@ -374,30 +869,16 @@ unsafe fn force_matrix_inner(device_index: usize, matrix: MatrixChoice) -> Resul
         Err(_) => println!("Current MF_MT_YUV_MATRIX: Not specified"),
     }
 
-    let target_value = match matrix {
-        MatrixChoice::Bt601 => MFVideoTransferMatrix_BT601.0 as u32,
-        MatrixChoice::Bt709 => MFVideoTransferMatrix_BT709.0 as u32,
-    };
-    let target_name = match matrix {
-        MatrixChoice::Bt601 => "BT.601",
-        MatrixChoice::Bt709 => "BT.709",
-    };
-
-    // Create new type with overridden matrix
-    let new_type = MFCreateMediaType().context("Failed to create media type")?;
-    current_type
-        .CopyAllItems(&new_type)
-        .context("Failed to copy media type attributes")?;
-    new_type
-        .SetUINT32(&MF_MT_YUV_MATRIX, target_value)
-        .context("Failed to set MF_MT_YUV_MATRIX")?;
+    let target_name = matrix.name();
+    let new_type = override_type(&current_type, matrix)?;
 
     match reader.SetCurrentMediaType(FIRST_VIDEO_STREAM, None, &new_type) {
         Ok(()) => {
             println!("Successfully set MF_MT_YUV_MATRIX to {target_name}.");
             println!();
             println!("Note: This override only affects this source reader session.");
-            println!("It does not persist after the program exits.");
+            println!("It does not persist after the program exits. Add --capture to see");
+            println!("whether the OS conversion follows it.");
         }
         Err(e) => {
             println!("Failed to set MF_MT_YUV_MATRIX to {target_name}: {e}");
@@ -410,6 +891,179 @@ unsafe fn force_matrix_inner(device_index: usize, matrix: MatrixChoice) -> Resul
     Ok(())
 }
 
+/// The `MF_MT_YUV_MATRIX` value for a matrix.
+fn matrix_attribute(matrix: MatrixChoice) -> u32 {
+    match matrix {
+        MatrixChoice::Bt601 => MFVideoTransferMatrix_BT601.0 as u32,
+        MatrixChoice::Bt709 => MFVideoTransferMatrix_BT709.0 as u32,
+    }
+}
+
+/// A copy of `media_type` with MF_MT_YUV_MATRIX set to `matrix`.
+unsafe fn override_type(media_type: &IMFMediaType, matrix: MatrixChoice) -> Result<IMFMediaType> {
+    let value = matrix_attribute(matrix);
+    let new_type = MFCreateMediaType().context("Failed to create media type")?;
+    media_type
+        .CopyAllItems(&new_type)
+        .context("Failed to copy media type attributes")?;
+    new_type
+        .SetUINT32(&MF_MT_YUV_MATRIX, value)
+        .context("Failed to set MF_MT_YUV_MATRIX")?;
+    Ok(new_type)
+}
+
+/// Capture a raw NV12 frame for our own decodes, then RGB from the source reader's own
+/// video processing twice: with the driver's media type and with the matrix overridden
+/// on it.
+unsafe fn force_matrix_capture_inner(
+    request: &CaptureRequest,
+    matrix: MatrixChoice,
+) -> Result<OverrideCapture> {
+    let frame = capture_frame_inner(request)?;
+    println!(
+        "Driver reports MF_MT_YUV_MATRIX: {}",
+        frame.colorspace.matrix
+    );
+    let converted_reported =
+        os_rgb_inner(request, None)?.context("The source reader returned no RGB frame")?;
+    let converted_forced = os_rgb_inner(request, Some(matrix))?;
+    Ok(OverrideCapture {
+        frame,
+        converted_reported,
+        converted_forced,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// OS RGB conversion
+// ---------------------------------------------------------------------------
+
+/// Capture through a source reader with video processing enabled, asking for RGB32 so
+/// the reader inserts the same converter apps get. It converts with the matrix the
+/// camera's media type carries: the driver's, or `matrix` set on it. `None` if the
+/// source rejects that override or doesn't keep it, as the OS would then never see it.
+unsafe fn os_rgb_inner(
+    request: &CaptureRequest,
+    matrix: Option<MatrixChoice>,
+) -> Result<Option<Vec<u8>>> {
+    let (source, name, _) = activate_device_by_index(request.device_index)?;
+    println!(
+        "Capturing OS-converted RGB from device {}: {}",
+        request.device_index + 1,
+        name
+    );
+
+    let (media_type, width, height) =
+        find_media_type(&source, request.pixel_format, request.resolution)?;
+    let native_type = match matrix {
+        Some(matrix) => override_type(&media_type, matrix)?,
+        None => media_type,
+    };
+
+    let mut attributes: Option<IMFAttributes> = None;
+    MFCreateAttributes(&mut attributes, 1).context("Failed to create MF attributes")?;
+    let attributes = attributes.unwrap();
+    attributes.SetUINT32(&MF_SOURCE_READER_ENABLE_VIDEO_PROCESSING, 1)?;
+
+    let reader = MFCreateSourceReaderFromMediaSource(&source, &attributes)
+        .context("Failed to create source reader")?;
+    // Pick the camera's native format first; asking for RGB32 afterwards adds a converter
+    // behind it instead of changing it
+    let accepted = reader.SetCurrentMediaType(FIRST_VIDEO_STREAM, None, &native_type);
+    match matrix {
+        None => accepted.context("Failed to set media type on reader")?,
+        Some(matrix) => {
+            // The source can take the type but keep its own attributes; the converter
+            // only sees what the reader ended up with
+            let kept = accepted.is_ok()
+                && reader
+                    .GetCurrentMediaType(FIRST_VIDEO_STREAM)
+                    .and_then(|t| t.GetUINT32(&MF_MT_YUV_MATRIX))
+                    .is_ok_and(|v| v == matrix_attribute(matrix));
+            if !kept {
+                println!(
+                    "The source {} MF_MT_YUV_MATRIX = {}.",
+                    if accepted.is_ok() {
+                        "took the media type but dropped"
+                    } else {
+                        "rejected"
+                    },
+                    matrix.name()
+                );
+                let _ = source.Shutdown();
+                return Ok(None);
+            }
+            println!("The source accepted MF_MT_YUV_MATRIX = {}.", matrix.name());
+        }
+    }
+    let rgb_type = MFCreateMediaType().context("Failed to create media type")?;
+    rgb_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+    rgb_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_RGB32)?;
+    reader
+        .SetCurrentMediaType(FIRST_VIDEO_STREAM, None, &rgb_type)
+        .with_context(|| {
+            format!(
+                "The source reader can't convert {} to RGB32",
+                request.pixel_format
+            )
+        })?;
+    let stride = reader
+        .GetCurrentMediaType(FIRST_VIDEO_STREAM)
+        .and_then(|t| t.GetUINT32(&MF_MT_DEFAULT_STRIDE))
+        .map_or((width * 4) as i32, |s| s as i32);
+
+    // RGB frames can't be measured for settling, so a few are simply skipped
+    let rgb_request = CaptureRequest {
+        pixel_format: "RGB32",
+        ..*request
+    };
+    let amps = ControlInterfaces {
+        camera: source.cast().ok(),
+        proc_amp: source.cast().ok(),
+    };
+    let result = controls::with_controls(
+        request.controls,
+        |control| amps.read(control),
+        |control, state| amps.write(control, state),
+        || read_frames(&reader, &rgb_request, width, height),
+    );
+
+    let _ = source.Shutdown();
+    let (data, _) = result?;
+    rgb32_to_rgb24(&data, width, height, stride).map(Some)
+}
+
+/// Top-down RGB24 from an RGB32 (BGRX) buffer; a negative stride means bottom-up rows.
+fn rgb32_to_rgb24(bgrx: &[u8], width: u32, height: u32, stride: i32) -> Result<Vec<u8>> {
+    let row_bytes = stride.unsigned_abs() as usize;
+    let needed = row_bytes * (height as usize - 1) + width as usize * 4;
+    if row_bytes < width as usize * 4 || bgrx.len() < needed {
+        anyhow::bail!(
+            "The source reader returned {} bytes with a stride of {}, expected {} for RGB32 {}x{}",
+            bgrx.len(),
+            stride,
+            needed,
+            width,
+            height
+        );
+    }
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..height as usize {
+        let row = if stride < 0 {
+            height as usize - 1 - y
+        } else {
+            y
+        };
+        let start = row * row_bytes;
+        rgb.extend(
+            bgrx[start..start + width as usize * 4]
+                .chunks_exact(4)
+                .flat_map(|px| [px[2], px[1], px[0]]),
+        );
+    }
+    Ok(rgb)
+}
+
 // ---------------------------------------------------------------------------
 // Format reading helpers
 // ---------------------------------------------------------------------------
@@ -429,18 +1083,17 @@ unsafe fn read_format(media_type: &IMFMediaType) -> Result<FormatInfo> {
         Err(_) => "Unknown".to_string(),
     };
 
-    let frame_rate = match media_type.GetUINT64(&MF_MT_FRAME_RATE) {
+    let frame_rates = match media_type.GetUINT64(&MF_MT_FRAME_RATE) {
         Ok(packed) => {
             let num = (packed >> 32) as u32;
             let den = packed as u32;
-            if den > 0 {
-                let fps = num as f64 / den as f64;
-                format!("{fps:.2} fps")
+            if num > 0 && den > 0 {
+                FrameRates::Discrete(vec![Rational { num, den }])
             } else {
-                "Unknown".to_string()
+                FrameRates::Unknown
             }
         }
-        Err(_) => "Unknown".to_string(),
+        Err(_) => FrameRates::Unknown,
     };
 
     let primaries = match media_type.GetUINT32(&MF_MT_VIDEO_PRIMARIES) {
@@ -466,7 +1119,8 @@ unsafe fn read_format(media_type: &IMFMediaType) -> Result<FormatInfo> {
     Ok(FormatInfo {
         pixel_format,
         resolution,
-        frame_rate,
+        size_range: None,
+        frame_rates,
         colorspace: ColorspaceInfo {
             primaries,
             matrix,
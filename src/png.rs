@@ -0,0 +1,60 @@
+/// Encode an RGB24 image as a PNG file in memory.
+///
+/// 8-bit truecolour, no interlacing. Every row uses the Sub filter, which suits both
+/// photos and the flat areas of plots, and the result is zlib-compressed.
+pub fn encode_png(width: u32, height: u32, rgb_data: &[u8]) -> Vec<u8> {
+    let row_bytes = width as usize * 3;
+    let mut filtered = Vec::with_capacity((row_bytes + 1) * height as usize);
+    for row in rgb_data.chunks_exact(row_bytes).take(height as usize) {
+        filtered.push(1); // filter type: Sub
+        for (i, &v) in row.iter().enumerate() {
+            let left = if i >= 3 { row[i - 3] } else { 0 };
+            filtered.push(v.wrapping_sub(left));
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.push(8); // bit depth
+    ihdr.push(2); // colour type: truecolour
+    ihdr.push(0); // compression: deflate
+    ihdr.push(0); // filter method: adaptive
+    ihdr.push(0); // interlace: none
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(
+        &mut png,
+        b"IDAT",
+        &miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 6),
+    );
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Length, type, data and a CRC-32 over type and data.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// CRC-32 (ISO 3309, polynomial 0xEDB88320) as PNG specifies it.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
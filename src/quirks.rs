@@ -0,0 +1,150 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::platform::UsbInfo;
+
+/// The quirks shipped with the tool. Entries in the user's file take precedence.
+const BUILTIN: &str = include_str!("../quirks.json");
+
+/// Environment variable naming the user's quirks file, overriding the default location.
+pub const PATH_VARIABLE: &str = "WEBCAM_COLORSPACE_QUIRKS";
+
+/// What a camera model's firmware really does, as confirmed by capture tests.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Quirk {
+    /// "VID:PID:bcdDevice" in hex, e.g. "046d:0825:0012"; "VID:PID" matches every revision.
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The matrix the driver reports, e.g. "BT.601".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advertised_matrix: Option<String>,
+    /// The matrix the firmware actually encodes with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_matrix: Option<String>,
+    /// "full" or "limited".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_range: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Quirk {
+    /// e.g. "firmware encodes BT.709 despite advertising BT.601, full range".
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (&self.actual_matrix, &self.advertised_matrix) {
+            (Some(actual), Some(advertised)) if actual != advertised => parts.push(format!(
+                "firmware encodes {actual} despite advertising {advertised}"
+            )),
+            (Some(actual), _) => parts.push(format!("firmware encodes {actual}")),
+            (None, _) => {}
+        }
+        if let Some(range) = &self.actual_range {
+            parts.push(format!("{range} range"));
+        }
+        if let Some(note) = &self.note {
+            parts.push(note.clone());
+        }
+        if parts.is_empty() {
+            "no details recorded".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct QuirksFile {
+    quirks: Vec<Quirk>,
+}
+
+pub struct Database {
+    user: Vec<Quirk>,
+    builtin: Vec<Quirk>,
+}
+
+impl Database {
+    /// The built-in quirks plus the user's file. A broken user file is reported and skipped.
+    pub fn load() -> Database {
+        let builtin = serde_json::from_str::<QuirksFile>(BUILTIN)
+            .expect("built-in quirks.json is valid")
+            .quirks;
+        let user = match user_path() {
+            Some(path) => read_file(&path).unwrap_or_else(|e| {
+                eprintln!("Warning: ignoring quirks file: {e:#}");
+                QuirksFile::default()
+            }),
+            None => QuirksFile::default(),
+        }
+        .quirks;
+        Database { user, builtin }
+    }
+
+    /// The most specific entry for a device: the user's before the built-in ones, and
+    /// within each an exact bcdDevice match before an entry for every revision.
+    pub fn lookup(&self, usb: &UsbInfo) -> Option<&Quirk> {
+        let exact = device_key(usb, true);
+        let any_revision = device_key(usb, false);
+        [&self.user, &self.builtin].into_iter().find_map(|quirks| {
+            let matching = |key: &str| quirks.iter().find(|q| q.device.eq_ignore_ascii_case(key));
+            matching(&exact).or_else(|| matching(&any_revision))
+        })
+    }
+}
+
+/// "VID:PID:bcdDevice", or "VID:PID" without the revision or if it is unknown.
+pub fn device_key(usb: &UsbInfo, with_revision: bool) -> String {
+    match &usb.bcd_device {
+        Some(bcd) if with_revision => format!("{}:{}:{}", usb.vendor_id, usb.product_id, bcd),
+        _ => format!("{}:{}", usb.vendor_id, usb.product_id),
+    }
+}
+
+/// `$WEBCAM_COLORSPACE_QUIRKS`, or quirks.json in the per-user config directory.
+pub fn user_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(PATH_VARIABLE) {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("webcam-colorspace").join("quirks.json"))
+}
+
+/// A missing file is an empty one.
+fn read_file(path: &Path) -> anyhow::Result<QuirksFile> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(QuirksFile::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    serde_json::from_str(&text).with_context(|| format!("Invalid quirks file {}", path.display()))
+}
+
+/// Add a quirk to the user's file, replacing any entry for the same device key.
+/// Returns the file written.
+pub fn add(quirk: Quirk) -> anyhow::Result<PathBuf> {
+    let path = user_path()
+        .context("No location for the quirks file: set WEBCAM_COLORSPACE_QUIRKS to a file path")?;
+    let mut file = read_file(&path)?;
+    file.quirks
+        .retain(|q| !q.device.eq_ignore_ascii_case(&quirk.device));
+    file.quirks.push(quirk);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut text = serde_json::to_string_pretty(&file)?;
+    text.push('\n');
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
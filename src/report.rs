@@ -0,0 +1,171 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::canvas::Canvas;
+use crate::png;
+
+/// Images wider than this are scaled down before they are inlined.
+const MAX_IMAGE_WIDTH: u32 = 1280;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; max-width: 1400px; }
+h1 { font-size: 1.6em; } h2 { font-size: 1.25em; margin-top: 2em; border-bottom: 1px solid #ccc; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f0f0f0; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }
+dt { font-weight: 600; } dd { margin: 0; }
+.figures { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; } figure img { max-width: 100%; display: block; border: 1px solid #ccc; }
+figcaption { font-size: 0.9em; color: #555; margin-top: 0.3em; }
+";
+
+/// A single self-contained HTML document, built section by section. Images are
+/// inlined as base64 PNG so the file can be attached to a bug report on its own.
+pub struct Report {
+    title: String,
+    body: String,
+    /// Consecutive images share one wrapping row.
+    in_figures: bool,
+}
+
+impl Report {
+    pub fn new(title: &str) -> Report {
+        Report {
+            title: title.to_string(),
+            body: String::new(),
+            in_figures: false,
+        }
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.close_figures();
+        let _ = writeln!(self.body, "<h2>{}</h2>", escape(text));
+    }
+
+    pub fn paragraph(&mut self, text: &str) {
+        self.close_figures();
+        let _ = writeln!(self.body, "<p>{}</p>", escape(text));
+    }
+
+    /// Label/value pairs.
+    pub fn facts(&mut self, facts: &[(&str, String)]) {
+        self.close_figures();
+        self.body.push_str("<dl>\n");
+        for (label, value) in facts {
+            let _ = writeln!(
+                self.body,
+                "<dt>{}</dt><dd>{}</dd>",
+                escape(label),
+                escape(value)
+            );
+        }
+        self.body.push_str("</dl>\n");
+    }
+
+    pub fn list(&mut self, items: &[String]) {
+        self.close_figures();
+        self.body.push_str("<ul>\n");
+        for item in items {
+            let _ = writeln!(self.body, "<li>{}</li>", escape(item));
+        }
+        self.body.push_str("</ul>\n");
+    }
+
+    pub fn table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        self.close_figures();
+        self.body.push_str("<table>\n<tr>");
+        for header in headers {
+            let _ = write!(self.body, "<th>{}</th>", escape(header));
+        }
+        self.body.push_str("</tr>\n");
+        for row in rows {
+            self.body.push_str("<tr>");
+            for cell in row {
+                let _ = write!(self.body, "<td>{}</td>", escape(cell));
+            }
+            self.body.push_str("</tr>\n");
+        }
+        self.body.push_str("</table>\n");
+    }
+
+    pub fn image(&mut self, caption: &str, image: &Canvas) {
+        if !self.in_figures {
+            self.body.push_str("<div class=\"figures\">\n");
+            self.in_figures = true;
+        }
+        let scaled;
+        let image = if image.width > MAX_IMAGE_WIDTH {
+            let height =
+                (image.height as u64 * MAX_IMAGE_WIDTH as u64 / image.width as u64).max(1) as u32;
+            let mut small = Canvas::new(MAX_IMAGE_WIDTH, height, [0, 0, 0]);
+            small.blit_scaled(image, 0, 0, MAX_IMAGE_WIDTH, height);
+            scaled = small;
+            &scaled
+        } else {
+            image
+        };
+        let png = png::encode_png(image.width, image.height, &image.data);
+        let _ = writeln!(
+            self.body,
+            "<figure><img src=\"data:image/png;base64,{}\" width=\"{}\" height=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>",
+            base64(&png),
+            image.width,
+            image.height,
+            escape(caption),
+            escape(caption)
+        );
+    }
+
+    pub fn write(mut self, path: &Path) -> anyhow::Result<()> {
+        self.close_figures();
+        let html = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{}</body>\n</html>\n",
+            self.body,
+            title = escape(&self.title)
+        );
+        std::fs::write(path, html).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn close_figures(&mut self) {
+        if self.in_figures {
+            self.body.push_str("</div>\n");
+            self.in_figures = false;
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Standard base64 with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,255 @@
+use crate::canvas::{Canvas, Rgb};
+use crate::histogram::PlaneHistograms;
+use crate::yuv::{self, YuvMatrix};
+
+/// Maximum plot width of the waveform and parade; wider frames are binned into columns.
+const MAX_PLOT_WIDTH: u32 = 768;
+const LEFT: u32 = 40;
+const TOP: u32 = 24;
+const BOTTOM: u32 = 8;
+const RIGHT: u32 = 8;
+
+const BACKGROUND: Rgb = [16, 16, 16];
+const GRATICULE: Rgb = [90, 90, 90];
+const LABEL: Rgb = [200, 200, 200];
+
+const BT601_TARGET: Rgb = [255, 160, 40];
+const BT709_TARGET: Rgb = [60, 200, 255];
+
+/// Luma waveform: one column per horizontal image position, one row per Y code value.
+pub fn luma_waveform(data: &[u8], width: u32, height: u32) -> Canvas {
+    let plot_w = width.min(MAX_PLOT_WIDTH);
+    let mut counts = vec![0u32; plot_w as usize * 256];
+
+    for row in 0..height as usize {
+        for col in 0..width as usize {
+            let y = data[row * width as usize + col] as usize;
+            let px = col * plot_w as usize / width as usize;
+            counts[(255 - y) * plot_w as usize + px] += 1;
+        }
+    }
+
+    let mut canvas = Canvas::new(LEFT + plot_w + RIGHT, TOP + 256 + BOTTOM, BACKGROUND);
+    canvas.text(LEFT as i64, 6, "Luma waveform (Y code values)", 2, LABEL);
+    draw_code_graticule(&mut canvas, plot_w);
+    plot_density(&mut canvas, &counts, plot_w, LEFT, [255, 255, 255]);
+    canvas
+}
+
+/// RGB parade: separate R, G and B waveforms side by side, from a frame decoded with `matrix`.
+pub fn rgb_parade(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    matrix: &YuvMatrix,
+    full_range: bool,
+) -> Canvas {
+    let rgb = yuv::nv12_to_rgb24(data, width, height, matrix, full_range);
+    let panel_w = (width.min(MAX_PLOT_WIDTH) / 3).max(1);
+    let gap = 8;
+    let plot_w = panel_w * 3 + gap * 2;
+
+    let mut canvas = Canvas::new(LEFT + plot_w + RIGHT, TOP + 256 + BOTTOM, BACKGROUND);
+    canvas.text(
+        LEFT as i64,
+        6,
+        &format!("RGB parade (decoded as {})", matrix.name),
+        2,
+        LABEL,
+    );
+    draw_code_graticule(&mut canvas, plot_w);
+
+    let tints: [Rgb; 3] = [[255, 60, 60], [60, 255, 60], [80, 120, 255]];
+    for (channel, tint) in tints.iter().enumerate() {
+        let mut counts = vec![0u32; panel_w as usize * 256];
+        for row in 0..height as usize {
+            for col in 0..width as usize {
+                let v = rgb[(row * width as usize + col) * 3 + channel] as usize;
+                let px = col * panel_w as usize / width as usize;
+                counts[(255 - v) * panel_w as usize + px] += 1;
+            }
+        }
+        let x0 = LEFT + channel as u32 * (panel_w + gap);
+        plot_density(&mut canvas, &counts, panel_w, x0, *tint);
+    }
+    canvas
+}
+
+/// Cb/Cr vectorscope with 75% colour bar targets for both BT.601 and BT.709.
+///
+/// A BT.709 signal decoded as BT.601 (or vice versa) lands between the two
+/// sets of targets, rotated and scaled relative to where it should be.
+pub fn vectorscope(data: &[u8], width: u32, height: u32, full_range: bool) -> Canvas {
+    const SIZE: u32 = 512;
+    let w = width as usize;
+    let h = height as usize;
+    let uv_plane = &data[w * h..w * h + w * (h / 2)];
+
+    let mut counts = vec![0u32; (SIZE * SIZE) as usize];
+    for uv in uv_plane.chunks_exact(2) {
+        let x = uv[0] as usize * 2;
+        let y = (255 - uv[1] as usize) * 2;
+        counts[y * SIZE as usize + x] += 1;
+    }
+
+    let mut canvas = Canvas::new(LEFT + SIZE + RIGHT, TOP + SIZE + BOTTOM + 20, BACKGROUND);
+    canvas.text(
+        LEFT as i64,
+        6,
+        "Vectorscope (Cb horizontal, Cr vertical)",
+        2,
+        LABEL,
+    );
+
+    let cx = (LEFT + SIZE / 2) as i64;
+    let cy = (TOP + SIZE / 2) as i64;
+    let chroma_scale = if full_range { 255.0 } else { 224.0 };
+
+    // Graticule: crosshair, 100% chroma circle and the skin tone line (123°)
+    canvas.line(LEFT as i64, cy, (LEFT + SIZE) as i64, cy, GRATICULE);
+    canvas.line(cx, TOP as i64, cx, (TOP + SIZE) as i64, GRATICULE);
+    // Normalized chroma spans ±0.5, i.e. ±chroma_scale/2 code values at 2 px per code
+    canvas.circle(cx, cy, chroma_scale as i64, GRATICULE);
+    let skin = 123f64.to_radians();
+    canvas.line(
+        cx,
+        cy,
+        cx + (skin.cos() * 240.0) as i64,
+        cy - (skin.sin() * 240.0) as i64,
+        [150, 110, 90],
+    );
+
+    plot_density(&mut canvas, &counts, SIZE, LEFT, [120, 255, 120]);
+
+    for (matrix, color) in [(&yuv::BT601, BT601_TARGET), (&yuv::BT709, BT709_TARGET)] {
+        for (label, rgb) in BAR_TARGETS {
+            let (cb, cr) = chroma_of(matrix, rgb);
+            let x = cx + (cb * chroma_scale * 2.0).round() as i64;
+            let y = cy - (cr * chroma_scale * 2.0).round() as i64;
+            canvas.rect_outline(x - 6, y - 6, 13, 13, color);
+            canvas.text(x + 9, y - 3, label, 1, color);
+        }
+    }
+
+    let legend_y = (TOP + SIZE + BOTTOM + 4) as i64;
+    canvas.text(LEFT as i64, legend_y, "BT.601 75% bars", 2, BT601_TARGET);
+    canvas.text(
+        LEFT as i64 + 220,
+        legend_y,
+        "BT.709 75% bars",
+        2,
+        BT709_TARGET,
+    );
+    canvas
+}
+
+/// Y, Cb and Cr histograms stacked, log-scaled, with the limited range bounds marked.
+pub fn histogram_plot(hist: &PlaneHistograms) -> Canvas {
+    const PANEL_H: u32 = 96;
+    const GAP: u32 = 28;
+    let plot_w = 512;
+
+    let mut canvas = Canvas::new(
+        LEFT + plot_w + RIGHT,
+        // Room below the last panel for its axis labels
+        TOP + 3 * (PANEL_H + GAP) + BOTTOM + 8,
+        BACKGROUND,
+    );
+    canvas.text(
+        LEFT as i64,
+        6,
+        "Histograms (code values, log scale)",
+        2,
+        LABEL,
+    );
+
+    let panels: [(&str, &[u64; 256], u32, Rgb); 3] = [
+        ("Y", &hist.y, 235, [230, 230, 230]),
+        ("Cb", &hist.cb, 240, [80, 120, 255]),
+        ("Cr", &hist.cr, 240, [255, 80, 80]),
+    ];
+    for (i, (name, counts, upper, tint)) in panels.into_iter().enumerate() {
+        let top = (TOP + GAP + i as u32 * (PANEL_H + GAP)) as i64;
+        let bottom = top + PANEL_H as i64 - 1;
+        canvas.text(4, top, name, 2, LABEL);
+
+        let norm = (1.0 + counts.iter().copied().max().unwrap_or(0) as f64).ln();
+        for (code, &n) in counts.iter().enumerate() {
+            if n == 0 {
+                continue;
+            }
+            let bar = ((1.0 + n as f64).ln() / norm * PANEL_H as f64).ceil() as u32;
+            let x = (LEFT + code as u32 * 2) as i64;
+            canvas.fill_rect(x, bottom + 1 - bar as i64, 2, bar, tint);
+        }
+
+        canvas.line(
+            LEFT as i64,
+            bottom + 1,
+            (LEFT + plot_w - 1) as i64,
+            bottom + 1,
+            GRATICULE,
+        );
+        for code in [16, upper] {
+            let x = (LEFT + code * 2) as i64;
+            canvas.line(x, top, x, bottom, [255, 200, 0]);
+            canvas.text(x - 6, bottom + 4, &code.to_string(), 1, LABEL);
+        }
+    }
+    canvas
+}
+
+/// 75% colour bars, as (label, normalized gamma-encoded R'G'B').
+const BAR_TARGETS: [(&str, [f64; 3]); 6] = [
+    ("R", [0.75, 0.0, 0.0]),
+    ("MG", [0.75, 0.0, 0.75]),
+    ("B", [0.0, 0.0, 0.75]),
+    ("CY", [0.0, 0.75, 0.75]),
+    ("G", [0.0, 0.75, 0.0]),
+    ("YL", [0.75, 0.75, 0.0]),
+];
+
+/// Normalized Cb/Cr (-0.5..0.5) of an RGB colour under `matrix`.
+fn chroma_of(matrix: &YuvMatrix, rgb: [f64; 3]) -> (f64, f64) {
+    let kg = 1.0 - matrix.kr - matrix.kb;
+    let y = matrix.kr * rgb[0] + kg * rgb[1] + matrix.kb * rgb[2];
+    let cb = (rgb[2] - y) / (2.0 * (1.0 - matrix.kb));
+    let cr = (rgb[0] - y) / (2.0 * (1.0 - matrix.kr));
+    (cb, cr)
+}
+
+/// Horizontal lines at the full and limited range bounds, labelled on the left.
+fn draw_code_graticule(canvas: &mut Canvas, plot_w: u32) {
+    for code in [0u32, 16, 128, 235, 255] {
+        let y = (TOP + 255 - code) as i64;
+        canvas.line(LEFT as i64, y, (LEFT + plot_w - 1) as i64, y, GRATICULE);
+        canvas.text(4, y - 3, &code.to_string(), 1, LABEL);
+    }
+}
+
+/// Composite a hit-count grid onto the canvas at (`x0`, TOP), log-scaled and tinted.
+fn plot_density(canvas: &mut Canvas, counts: &[u32], plot_w: u32, x0: u32, tint: Rgb) {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+    let norm = (1.0 + max as f64).ln();
+    let rows = counts.len() / plot_w as usize;
+
+    for row in 0..rows {
+        for col in 0..plot_w as usize {
+            let c = counts[row * plot_w as usize + col];
+            if c == 0 {
+                continue;
+            }
+            // Keep single hits visible
+            let level = 0.25 + 0.75 * (1.0 + c as f64).ln() / norm;
+            let px = [
+                (tint[0] as f64 * level) as u8,
+                (tint[1] as f64 * level) as u8,
+                (tint[2] as f64 * level) as u8,
+            ];
+            canvas.set_pixel((x0 as usize + col) as i64, (TOP as usize + row) as i64, px);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+/// How long to let auto-exposure and white balance settle before keeping frames.
+#[derive(Clone, Copy)]
+pub enum SettlePolicy {
+    /// Discard a fixed number of frames.
+    Fixed(usize),
+    /// Wait until mean Y, Cb and Cr each change by at most `tolerance` code values
+    /// between consecutive frames, `stable_frames` times in a row, or `timeout` elapses.
+    Adaptive {
+        tolerance: f64,
+        stable_frames: usize,
+        timeout: Duration,
+    },
+}
+
+pub const DEFAULT_TOLERANCE: f64 = 1.0;
+pub const DEFAULT_STABLE_FRAMES: usize = 3;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Default for SettlePolicy {
+    fn default() -> Self {
+        SettlePolicy::Adaptive {
+            tolerance: DEFAULT_TOLERANCE,
+            stable_frames: DEFAULT_STABLE_FRAMES,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// Frames discarded when the policy is adaptive but the frames can't be measured (MJPG).
+const UNMEASURED_SKIP: usize = 4;
+
+/// Tracks a stream's frames against a [`SettlePolicy`].
+pub struct Settle {
+    policy: SettlePolicy,
+    started: Instant,
+    frames: usize,
+    previous: Option<[f64; 3]>,
+    stable: usize,
+    outcome: Option<Outcome>,
+}
+
+enum Outcome {
+    Fixed,
+    Settled { tolerance: f64 },
+    TimedOut { timeout: Duration },
+    Unmeasured,
+}
+
+impl Settle {
+    pub fn new(policy: SettlePolicy) -> Self {
+        Settle {
+            policy,
+            started: Instant::now(),
+            frames: 0,
+            previous: None,
+            stable: 0,
+            outcome: None,
+        }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// Feed the next frame's mean Y/Cb/Cr (`None` if its pixels can't be measured).
+    /// Returns true once the camera has settled; that frame and all later ones are usable.
+    pub fn observe(&mut self, means: Option<[f64; 3]>) -> bool {
+        if self.outcome.is_some() {
+            return true;
+        }
+        self.frames += 1;
+
+        self.outcome = match (self.policy, means) {
+            (SettlePolicy::Fixed(skip), _) => (self.frames > skip).then_some(Outcome::Fixed),
+            (SettlePolicy::Adaptive { .. }, None) => {
+                (self.frames > UNMEASURED_SKIP).then_some(Outcome::Unmeasured)
+            }
+            (
+                SettlePolicy::Adaptive {
+                    tolerance,
+                    stable_frames,
+                    timeout,
+                },
+                Some(means),
+            ) => {
+                let steady = self.previous.is_some_and(|prev| {
+                    prev.iter()
+                        .zip(means)
+                        .all(|(a, b)| (a - b).abs() <= tolerance)
+                });
+                self.stable = if steady { self.stable + 1 } else { 0 };
+                self.previous = Some(means);
+
+                if self.stable >= stable_frames {
+                    Some(Outcome::Settled { tolerance })
+                } else if self.started.elapsed() >= timeout {
+                    Some(Outcome::TimedOut { timeout })
+                } else {
+                    None
+                }
+            }
+        };
+        self.outcome.is_some()
+    }
+
+    /// One line describing how settling went, for the capture log.
+    pub fn summary(&self) -> String {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let skipped = self.frames.saturating_sub(1);
+        match self.outcome {
+            None => format!("Still settling after {} frames", self.frames),
+            Some(Outcome::Fixed) => format!("Skipped {skipped} frames (fixed settle)"),
+            Some(Outcome::Unmeasured) => {
+                format!("Skipped {skipped} frames (compressed frames can't be measured)")
+            }
+            Some(Outcome::Settled { tolerance }) => format!(
+                "Settled after {} frames ({elapsed:.2} s): mean Y/Cb/Cr steady within {tolerance}",
+                self.frames
+            ),
+            Some(Outcome::TimedOut { timeout }) => format!(
+                "Warning: camera did not settle within {:.1} s ({} frames); capturing anyway",
+                timeout.as_secs_f64(),
+                self.frames
+            ),
+        }
+    }
+}
+
+/// Mean Y, Cb and Cr of an NV12 frame, from every 4th sample of every 4th row.
+/// `None` if the buffer is too short for the frame size.
+pub fn nv12_means(data: &[u8], width: u32, height: u32) -> Option<[f64; 3]> {
+    let w = width as usize;
+    let h = height as usize;
+    if data.len() < w * h + w * (h / 2) {
+        return None;
+    }
+    let mut y_sum = 0u64;
+    let mut y_count = 0u64;
+    for row in (0..h).step_by(4) {
+        for &v in data[row * w..(row + 1) * w].iter().step_by(4) {
+            y_sum += v as u64;
+            y_count += 1;
+        }
+    }
+
+    let uv_plane = &data[w * h..w * h + w * (h / 2)];
+    let (mut cb_sum, mut cr_sum, mut c_count) = (0u64, 0u64, 0u64);
+    for row in (0..h / 2).step_by(4) {
+        for uv in uv_plane[row * w..(row + 1) * w].chunks_exact(2).step_by(4) {
+            cb_sum += uv[0] as u64;
+            cr_sum += uv[1] as u64;
+            c_count += 1;
+        }
+    }
+
+    let mean = |sum: u64, count: u64| sum as f64 / count.max(1) as f64;
+    Some([
+        mean(y_sum, y_count),
+        mean(cb_sum, c_count),
+        mean(cr_sum, c_count),
+    ])
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::platform::{DeviceInfo, FormatInfo};
+
+/// An enumeration as `--json` prints it and `snapshot` saves it.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub os: String,
+    pub devices: Vec<DeviceInfo>,
+}
+
+impl Snapshot {
+    pub fn load(path: &Path) -> anyhow::Result<Snapshot> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("{} is not an enumeration snapshot", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// What changed for one device between two enumerations.
+pub struct DeviceDiff {
+    pub device: String,
+    pub kind: DiffKind,
+}
+
+pub enum DiffKind {
+    Added,
+    Removed,
+    /// One line per change: "+ format", "- format" or "format: field old -> new".
+    Changed(Vec<String>),
+}
+
+impl DiffKind {
+    /// Number of changes, for the exit status.
+    pub fn count(&self) -> usize {
+        match self {
+            DiffKind::Added | DiffKind::Removed => 1,
+            DiffKind::Changed(changes) => changes.len(),
+        }
+    }
+}
+
+/// Devices that were added, removed or changed, in the order of `current` followed by
+/// those only in `baseline`.
+///
+/// Devices are matched by USB identity (VID:PID and serial number) where known, since
+/// device paths move around between boots, and by name otherwise. Formats are matched
+/// by pixel format, size and frame rates.
+pub fn diff(baseline: &[DeviceInfo], current: &[DeviceInfo]) -> Vec<DeviceDiff> {
+    let before = keyed(baseline);
+    let after = keyed(current);
+    let mut diffs = Vec::new();
+
+    for (key, new) in &after {
+        let kind = match before.iter().find(|(k, _)| k == key) {
+            Some((_, old)) => DiffKind::Changed(device_changes(old, new)),
+            None => DiffKind::Added,
+        };
+        if kind.count() > 0 {
+            diffs.push(DeviceDiff {
+                device: label(new),
+                kind,
+            });
+        }
+    }
+    for (key, old) in &before {
+        if !after.iter().any(|(k, _)| k == key) {
+            diffs.push(DeviceDiff {
+                device: label(old),
+                kind: DiffKind::Removed,
+            });
+        }
+    }
+    diffs
+}
+
+/// Each device with the key it is matched by. Identical cameras without a serial
+/// number are told apart by their order.
+fn keyed(devices: &[DeviceInfo]) -> Vec<(String, &DeviceInfo)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    devices
+        .iter()
+        .map(|d| {
+            let key = match &d.usb {
+                Some(usb) => format!(
+                    "{}:{}:{}",
+                    usb.vendor_id.to_lowercase(),
+                    usb.product_id.to_lowercase(),
+                    usb.serial.as_deref().unwrap_or("")
+                ),
+                None => d.name.clone(),
+            };
+            let n = seen.entry(key.clone()).or_default();
+            *n += 1;
+            (format!("{key}#{n}"), d)
+        })
+        .collect()
+}
+
+fn label(device: &DeviceInfo) -> String {
+    match &device.usb {
+        Some(usb) => format!("{} ({}:{})", device.name, usb.vendor_id, usb.product_id),
+        None => device.name.clone(),
+    }
+}
+
+fn device_changes(old: &DeviceInfo, new: &DeviceInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.name != new.name {
+        changes.push(format!("name: {} -> {}", old.name, new.name));
+    }
+    let revision = |d: &DeviceInfo| d.usb.as_ref().and_then(|u| u.bcd_device.clone());
+    if let (Some(old_rev), Some(new_rev)) = (revision(old), revision(new)) {
+        if old_rev != new_rev {
+            changes.push(format!(
+                "firmware revision (bcdDevice): {old_rev} -> {new_rev}"
+            ));
+        }
+    }
+
+    let old_formats = formats(&old.formats);
+    let new_formats = formats(&new.formats);
+    for (key, format) in &new_formats {
+        match old_formats.iter().find(|(k, _)| k == key) {
+            None => changes.push(format!("+ {key} ({})", summary(format))),
+            Some((_, was)) => {
+                let (a, b) = (&was.colorspace, &format.colorspace);
+                for (field, before, after) in [
+                    ("primaries", &a.primaries, &b.primaries),
+                    ("matrix", &a.matrix, &b.matrix),
+                    ("transfer", &a.transfer, &b.transfer),
+                    ("range", &a.range, &b.range),
+                ] {
+                    if before != after {
+                        changes.push(format!("{key}: {field} {before} -> {after}"));
+                    }
+                }
+            }
+        }
+    }
+    for (key, format) in &old_formats {
+        if !new_formats.iter().any(|(k, _)| k == key) {
+            changes.push(format!("- {key} ({})", summary(format)));
+        }
+    }
+    changes
+}
+
+/// Formats keyed by "NV12 1280x720 @ 30.00 fps", without duplicates.
+fn formats(formats: &[FormatInfo]) -> Vec<(String, &FormatInfo)> {
+    let mut keyed: Vec<(String, &FormatInfo)> = Vec::new();
+    for format in formats {
+        let key = format!(
+            "{} {} @ {}",
+            format.pixel_format, format.resolution, format.frame_rates
+        );
+        if !keyed.iter().any(|(k, _)| *k == key) {
+            keyed.push((key, format));
+        }
+    }
+    keyed
+}
+
+fn summary(format: &FormatInfo) -> String {
+    let cs = &format.colorspace;
+    format!(
+        "primaries {}, matrix {}, transfer {}, range {}",
+        cs.primaries, cs.matrix, cs.transfer, cs.range
+    )
+}
@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+/// Frame-to-frame noise of an NV12 sequence, in code values.
+///
+/// Each figure is the RMS over samples of that sample's standard deviation across frames.
+pub struct TemporalNoise {
+    pub frames: usize,
+    pub y: f64,
+    pub cb: f64,
+    pub cr: f64,
+    /// Luma noise of shadows (mean Y < 64), midtones and highlights (mean Y >= 192);
+    /// `None` where the scene has no such pixels.
+    pub y_by_level: [Option<f64>; 3],
+}
+
+pub const LEVEL_NAMES: [&str; 3] = ["shadows", "midtones", "highlights"];
+
+/// Per-sample mean of several NV12 frames of the same size, rounded to nearest.
+pub fn average(frames: &[&[u8]]) -> Vec<u8> {
+    let n = frames.len() as u32;
+    let mut sums = vec![0u32; frames[0].len()];
+    for frame in frames {
+        for (sum, &v) in sums.iter_mut().zip(frame.iter()) {
+            *sum += v as u32;
+        }
+    }
+    sums.into_iter().map(|s| ((s + n / 2) / n) as u8).collect()
+}
+
+/// Measure temporal noise over at least two NV12 frames of the same size.
+pub fn nv12_noise(frames: &[&[u8]], width: u32, height: u32) -> TemporalNoise {
+    let w = width as usize;
+    let h = height as usize;
+    let y_len = w * h;
+    let uv_len = w * (h / 2);
+    let n = frames.len() as f64;
+
+    // Mean and sample variance (n - 1) at byte offset `i`
+    let stats = |i: usize| {
+        let (mut sum, mut sum_sq) = (0.0, 0.0);
+        for frame in frames {
+            let v = frame[i] as f64;
+            sum += v;
+            sum_sq += v * v;
+        }
+        let mean = sum / n;
+        (mean, ((sum_sq - sum * mean) / (n - 1.0)).max(0.0))
+    };
+
+    let mut y_var = 0.0;
+    let mut level_var = [0.0f64; 3];
+    let mut level_count = [0usize; 3];
+    for i in 0..y_len {
+        let (mean, var) = stats(i);
+        y_var += var;
+        let level = if mean < 64.0 {
+            0
+        } else if mean < 192.0 {
+            1
+        } else {
+            2
+        };
+        level_var[level] += var;
+        level_count[level] += 1;
+    }
+
+    let (mut cb_var, mut cr_var) = (0.0, 0.0);
+    for i in (y_len..y_len + uv_len).step_by(2) {
+        cb_var += stats(i).1;
+        cr_var += stats(i + 1).1;
+    }
+
+    let chroma_samples = (uv_len / 2).max(1) as f64;
+    TemporalNoise {
+        frames: frames.len(),
+        y: (y_var / y_len.max(1) as f64).sqrt(),
+        cb: (cb_var / chroma_samples).sqrt(),
+        cr: (cr_var / chroma_samples).sqrt(),
+        y_by_level: [0, 1, 2]
+            .map(|l| (level_count[l] > 0).then(|| (level_var[l] / level_count[l] as f64).sqrt())),
+    }
+}
+
+/// Colour statistics of one frame of a recording.
+pub struct FrameStats {
+    /// Capture time relative to the first frame.
+    pub timestamp: Duration,
+    pub y: f64,
+    pub cb: f64,
+    pub cr: f64,
+    /// RMS distance of Cb/Cr from neutral: how saturated the frame is as encoded.
+    pub saturation: f64,
+    /// Share of luma samples outside 16-235, in percent.
+    pub y_outside: f64,
+}
+
+/// Statistics of an NV12 frame, from every other sample in each direction.
+pub fn frame_stats(data: &[u8], width: u32, height: u32, timestamp: Duration) -> FrameStats {
+    let w = width as usize;
+    let h = height as usize;
+    let (mut y_sum, mut outside, mut y_count) = (0u64, 0u64, 0u64);
+    for row in (0..h).step_by(2) {
+        for &v in data[row * w..(row + 1) * w].iter().step_by(2) {
+            y_sum += v as u64;
+            outside += u64::from(!(16..=235).contains(&v));
+            y_count += 1;
+        }
+    }
+
+    let uv_plane = &data[w * h..w * h + w * (h / 2)];
+    let (mut cb_sum, mut cr_sum, mut sq_sum, mut c_count) = (0u64, 0u64, 0u64, 0u64);
+    for row in (0..h / 2).step_by(2) {
+        for uv in uv_plane[row * w..(row + 1) * w].chunks_exact(2).step_by(2) {
+            let (cb, cr) = (uv[0] as i64 - 128, uv[1] as i64 - 128);
+            cb_sum += uv[0] as u64;
+            cr_sum += uv[1] as u64;
+            sq_sum += (cb * cb + cr * cr) as u64;
+            c_count += 1;
+        }
+    }
+
+    let (y_count, c_count) = (y_count.max(1) as f64, c_count.max(1) as f64);
+    FrameStats {
+        timestamp,
+        y: y_sum as f64 / y_count,
+        cb: cb_sum as f64 / c_count,
+        cr: cr_sum as f64 / c_count,
+        saturation: (sq_sum as f64 / c_count).sqrt(),
+        y_outside: 100.0 * outside as f64 / y_count,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    /// Chroma jumped while luma held steady: the colour processing changed.
+    Colour,
+    /// Luma went from limited-range levels to full-range ones, or back.
+    Range,
+    /// Chroma jumped together with luma, as with an exposure step or a scene cut.
+    Scene,
+    /// Frames are missing from the timestamps.
+    Gap,
+}
+
+/// A sudden change in a recording: between two consecutive frames, or over a few frames
+/// in a row.
+pub struct Change {
+    pub kind: ChangeKind,
+    /// The first frame after the change starts and the last one it affects.
+    pub first_frame: usize,
+    pub last_frame: usize,
+    pub description: String,
+}
+
+/// Smallest jump in mean Cb or Cr, in code values, that counts as sudden.
+const CHROMA_STEP: f64 = 1.5;
+/// Smallest relative jump in saturation that counts as sudden.
+const SATURATION_STEP: f64 = 0.06;
+/// A jump must also be this many times the recording's typical frame-to-frame change.
+const NOISE_FACTOR: f64 = 6.0;
+/// Luma moving less than this (code values) counts as steady.
+const LUMA_STEADY: f64 = 3.0;
+/// Luma outside 16-235 on fewer than this share of samples (percent) looks limited range,
+/// on more than `FULL_RANGE_OUTSIDE` full range.
+const LIMITED_RANGE_OUTSIDE: f64 = 0.1;
+const FULL_RANGE_OUTSIDE: f64 = 1.0;
+/// An interval this many times the median one means dropped frames.
+const GAP_FACTOR: f64 = 2.5;
+
+/// Sudden changes in a recording's per-frame statistics, in order. Gradual drift, such
+/// as auto-exposure and white balance adapting, is not flagged.
+pub fn find_changes(stats: &[FrameStats]) -> Vec<Change> {
+    let chroma_step = |a: &FrameStats, b: &FrameStats| (b.cb - a.cb).abs().max((b.cr - a.cr).abs());
+    let saturation_step = |a: &FrameStats, b: &FrameStats| {
+        (b.saturation - a.saturation).abs() / a.saturation.max(1.0)
+    };
+    let interval =
+        |a: &FrameStats, b: &FrameStats| b.timestamp.saturating_sub(a.timestamp).as_secs_f64();
+    let typical = |step: &dyn Fn(&FrameStats, &FrameStats) -> f64| {
+        median(stats.windows(2).map(|w| step(&w[0], &w[1])).collect())
+    };
+    let chroma_limit = CHROMA_STEP.max(NOISE_FACTOR * typical(&chroma_step));
+    let saturation_limit = SATURATION_STEP.max(NOISE_FACTOR * typical(&saturation_step));
+    let median_interval = typical(&interval);
+    let limited = |s: &FrameStats| s.y_outside < LIMITED_RANGE_OUTSIDE;
+    let full = |s: &FrameStats| s.y_outside > FULL_RANGE_OUTSIDE;
+
+    let mut changes: Vec<Change> = Vec::new();
+    for frame in 1..stats.len() {
+        let (a, b) = (&stats[frame - 1], &stats[frame]);
+        let mut kinds = Vec::new();
+        if chroma_step(a, b) > chroma_limit || saturation_step(a, b) > saturation_limit {
+            kinds.push(if (b.y - a.y).abs() < LUMA_STEADY {
+                ChangeKind::Colour
+            } else {
+                ChangeKind::Scene
+            });
+        }
+        if (limited(a) && full(b)) || (full(a) && limited(b)) {
+            kinds.push(ChangeKind::Range);
+        }
+        if median_interval > 0.0 && interval(a, b) > GAP_FACTOR * median_interval {
+            kinds.push(ChangeKind::Gap);
+        }
+
+        for kind in kinds {
+            // The same kind of change on the previous frame continues that transition
+            match changes
+                .iter_mut()
+                .find(|c| c.kind == kind && c.last_frame + 1 == frame)
+            {
+                Some(change) => change.last_frame = frame,
+                None => changes.push(Change {
+                    kind,
+                    first_frame: frame,
+                    last_frame: frame,
+                    description: String::new(),
+                }),
+            }
+        }
+    }
+
+    for change in &mut changes {
+        let (a, b) = (&stats[change.first_frame - 1], &stats[change.last_frame]);
+        change.description = match change.kind {
+            ChangeKind::Colour | ChangeKind::Scene => format!(
+                "Cb {:.1} -> {:.1}, Cr {:.1} -> {:.1}, saturation {:.1} -> {:.1} ({:+.0}%), Y {:.1} -> {:.1}",
+                a.cb,
+                b.cb,
+                a.cr,
+                b.cr,
+                a.saturation,
+                b.saturation,
+                100.0 * (b.saturation - a.saturation) / a.saturation.max(1.0),
+                a.y,
+                b.y
+            ),
+            ChangeKind::Range => format!(
+                "luma outside 16-235: {:.2}% -> {:.2}%",
+                a.y_outside, b.y_outside
+            ),
+            // Merged gaps span from the frame before the first through the last frame
+            ChangeKind::Gap if change.first_frame == change.last_frame => format!(
+                "{:.0} ms between frames {} and {}, typically {:.0} ms",
+                interval(a, b) * 1000.0,
+                change.first_frame,
+                change.last_frame + 1,
+                median_interval * 1000.0
+            ),
+            ChangeKind::Gap => format!(
+                "{:.0} ms from frame {} to frame {} over {} long intervals, typically {:.0} ms each",
+                interval(a, b) * 1000.0,
+                change.first_frame,
+                change.last_frame + 1,
+                change.last_frame + 1 - change.first_frame,
+                median_interval * 1000.0
+            ),
+        };
+    }
+    changes
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steady frames at 30 fps, except for the given extra delays before some frames.
+    fn recording(frames: usize, delays_ms: &[(usize, u64)]) -> Vec<FrameStats> {
+        let mut at = 0;
+        (0..frames)
+            .map(|frame| {
+                at += if frame == 0 { 0 } else { 33 };
+                at += delays_ms
+                    .iter()
+                    .find(|&&(f, _)| f == frame)
+                    .map_or(0, |&(_, d)| d);
+                FrameStats {
+                    timestamp: Duration::from_millis(at),
+                    y: 120.0,
+                    cb: 128.0,
+                    cr: 128.0,
+                    saturation: 10.0,
+                    y_outside: 0.0,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consecutive_gaps_are_reported_over_the_merged_frames() {
+        let changes = find_changes(&recording(40, &[(10, 100), (11, 100), (12, 100)]));
+        assert_eq!(changes.len(), 1);
+        let gap = &changes[0];
+        assert!(gap.kind == ChangeKind::Gap);
+        assert_eq!((gap.first_frame, gap.last_frame), (10, 12));
+        assert_eq!(
+            gap.description,
+            "399 ms from frame 10 to frame 13 over 3 long intervals, typically 33 ms each"
+        );
+    }
+
+    #[test]
+    fn a_single_gap_names_the_frames_either_side() {
+        let changes = find_changes(&recording(40, &[(20, 100)]));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].description,
+            "133 ms between frames 20 and 21, typically 33 ms"
+        );
+    }
+}
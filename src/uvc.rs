@@ -0,0 +1,211 @@
+// Descriptor types and class codes from the USB 2.0 and UVC 1.5 specifications
+const INTERFACE: u8 = 0x04;
+const CS_INTERFACE: u8 = 0x24;
+
+const CC_VIDEO: u8 = 0x0e;
+const SC_VIDEOCONTROL: u8 = 0x01;
+const SC_VIDEOSTREAMING: u8 = 0x02;
+
+const VC_HEADER: u8 = 0x01;
+
+const VS_FORMAT_UNCOMPRESSED: u8 = 0x04;
+const VS_FRAME_UNCOMPRESSED: u8 = 0x05;
+const VS_FORMAT_MJPEG: u8 = 0x06;
+const VS_FRAME_MJPEG: u8 = 0x07;
+const VS_COLORFORMAT: u8 = 0x0d;
+const VS_FORMAT_FRAME_BASED: u8 = 0x10;
+const VS_FRAME_FRAME_BASED: u8 = 0x11;
+
+/// Split a descriptor blob, such as sysfs `descriptors` (the device descriptor followed
+/// by the raw configuration descriptors), into individual descriptors. Each starts with
+/// its length and type; iteration stops at the first malformed length.
+fn descriptors(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let len = *rest.first()? as usize;
+        if len < 2 || len > rest.len() {
+            return None;
+        }
+        let (descriptor, tail) = rest.split_at(len);
+        rest = tail;
+        Some(descriptor)
+    })
+}
+
+/// A VideoStreaming format descriptor with its frames and Color Matching descriptor.
+pub struct StreamFormat {
+    /// bInterfaceNumber of the VideoStreaming interface.
+    pub interface: u8,
+    /// bFormatIndex, as the host selects it in a probe request.
+    pub index: u8,
+    /// "MJPG", or the FourCC leading the format GUID, e.g. "YUY2" or "NV12".
+    pub fourcc: String,
+    pub frame_sizes: Vec<(u16, u16)>,
+    /// `None` if the firmware omits the descriptor, in which case [`ColorMatching::DEFAULT`] applies.
+    pub color_matching: Option<ColorMatching>,
+}
+
+impl StreamFormat {
+    pub fn color(&self) -> ColorMatching {
+        self.color_matching.unwrap_or(ColorMatching::DEFAULT)
+    }
+}
+
+/// bColorPrimaries, bTransferCharacteristics and bMatrixCoefficients of a VS Color
+/// Matching descriptor.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ColorMatching {
+    pub primaries: u8,
+    pub transfer: u8,
+    pub matrix: u8,
+}
+
+impl ColorMatching {
+    /// What the spec says applies to a format without a Color Matching descriptor.
+    pub const DEFAULT: ColorMatching = ColorMatching {
+        primaries: 1,
+        transfer: 1,
+        matrix: 4,
+    };
+
+    pub fn primaries_name(self) -> String {
+        match self.primaries {
+            0 => "Unspecified".to_string(),
+            1 => "BT.709".to_string(),
+            2 => "BT.470-2 (M)".to_string(),
+            3 => "BT.470-2 (B, G)".to_string(),
+            4 => "SMPTE 170M".to_string(),
+            5 => "SMPTE 240M".to_string(),
+            v => format!("Reserved ({v})"),
+        }
+    }
+
+    pub fn transfer_name(self) -> String {
+        match self.transfer {
+            0 => "Unspecified".to_string(),
+            1 => "BT.709".to_string(),
+            2 => "BT.470-2 (M)".to_string(),
+            3 => "BT.470-2 (B, G)".to_string(),
+            4 => "SMPTE 170M".to_string(),
+            5 => "SMPTE 240M".to_string(),
+            6 => "Linear".to_string(),
+            7 => "sRGB".to_string(),
+            v => format!("Reserved ({v})"),
+        }
+    }
+
+    pub fn matrix_name(self) -> String {
+        match self.matrix {
+            0 => "Unspecified".to_string(),
+            1 => "BT.709".to_string(),
+            2 => "FCC".to_string(),
+            3 => "BT.470-2 (B, G)".to_string(),
+            4 => "SMPTE 170M (BT.601)".to_string(),
+            5 => "SMPTE 240M".to_string(),
+            v => format!("Reserved ({v})"),
+        }
+    }
+
+    /// The matrix under the name the OS backends report it by, if it has one.
+    /// BT.470-2 B/G uses the same coefficients as BT.601.
+    pub fn matrix_family(self) -> Option<&'static str> {
+        match self.matrix {
+            1 => Some("BT.709"),
+            3 | 4 => Some("BT.601"),
+            5 => Some("SMPTE 240M"),
+            _ => None,
+        }
+    }
+}
+
+/// The VideoStreaming formats in a descriptor blob, in descriptor order. Colour
+/// matching descriptors follow the frames of the format they describe.
+pub fn stream_formats(data: &[u8]) -> Vec<StreamFormat> {
+    let mut formats: Vec<StreamFormat> = Vec::new();
+    // bInterfaceNumber of the VideoStreaming interface being walked, if any
+    let mut interface = None;
+    for d in descriptors(data) {
+        match d[1] {
+            INTERFACE if d.len() >= 7 => {
+                interface = (d[5] == CC_VIDEO && d[6] == SC_VIDEOSTREAMING).then_some(d[2]);
+            }
+            CS_INTERFACE if d.len() >= 4 => {
+                let Some(interface) = interface else {
+                    continue;
+                };
+                let fourcc = match d[2] {
+                    VS_FORMAT_UNCOMPRESSED | VS_FORMAT_FRAME_BASED if d.len() >= 21 => {
+                        Some(guid_fourcc(&d[5..9]))
+                    }
+                    VS_FORMAT_MJPEG => Some("MJPG".to_string()),
+                    _ => None,
+                };
+                if let Some(fourcc) = fourcc {
+                    formats.push(StreamFormat {
+                        interface,
+                        index: d[3],
+                        fourcc,
+                        frame_sizes: Vec::new(),
+                        color_matching: None,
+                    });
+                    continue;
+                }
+
+                let Some(format) = formats.last_mut().filter(|f| f.interface == interface) else {
+                    continue;
+                };
+                match d[2] {
+                    VS_FRAME_UNCOMPRESSED | VS_FRAME_MJPEG | VS_FRAME_FRAME_BASED
+                        if d.len() >= 9 =>
+                    {
+                        format.frame_sizes.push((
+                            u16::from_le_bytes([d[5], d[6]]),
+                            u16::from_le_bytes([d[7], d[8]]),
+                        ));
+                    }
+                    VS_COLORFORMAT if d.len() >= 6 => {
+                        format.color_matching = Some(ColorMatching {
+                            primaries: d[3],
+                            transfer: d[4],
+                            matrix: d[5],
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    formats
+}
+
+/// Format GUIDs are the FourCC followed by the fixed suffix 0000-0010-8000-00AA00389B71.
+fn guid_fourcc(code: &[u8]) -> String {
+    if code.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        String::from_utf8_lossy(code).trim_end().to_string()
+    } else {
+        code.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+/// bcdUVC from the first VideoControl interface header, e.g. 0x0110 for UVC 1.1.
+pub fn uvc_version(data: &[u8]) -> Option<u16> {
+    let mut in_video_control = false;
+    for d in descriptors(data) {
+        match d[1] {
+            INTERFACE if d.len() >= 7 => {
+                in_video_control = d[5] == CC_VIDEO && d[6] == SC_VIDEOCONTROL;
+            }
+            CS_INTERFACE if in_video_control && d.len() >= 5 && d[2] == VC_HEADER => {
+                return Some(u16::from_le_bytes([d[3], d[4]]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Format a binary-coded decimal version such as bcdUVC or bcdDevice: 0x0110 -> "1.10".
+pub fn bcd_version(bcd: u16) -> String {
+    format!("{:x}.{:02x}", bcd >> 8, bcd & 0xff)
+}
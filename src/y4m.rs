@@ -0,0 +1,233 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::yuv;
+
+/// What starts every frame; frame parameters after `FRAME` are not supported.
+const FRAME_HEADER: &[u8] = b"FRAME\n";
+
+/// A YUV4MPEG2 file of 8-bit 4:2:0 frames, read one frame at a time by index and returned
+/// as NV12 like everything else here, so a long recording is never loaded whole.
+pub struct Y4mReader {
+    file: File,
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second as a ratio, if the file states one.
+    pub frame_rate: Option<(u32, u32)>,
+    /// From `XCOLORRANGE`; `None` if the file doesn't say.
+    pub full_range: Option<bool>,
+    /// Number of frames in the file.
+    pub frames: usize,
+    /// Bytes up to the first frame.
+    header_len: u64,
+    /// Planar 4:2:0 bytes per frame, without the FRAME line.
+    frame_size: usize,
+}
+
+/// Writes NV12 frames to a YUV4MPEG2 file one at a time, as planar 4:2:0 (`C420`).
+pub struct Y4mWriter {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    /// NV12 bytes per frame.
+    frame_size: usize,
+    frames: usize,
+}
+
+impl Y4mWriter {
+    /// Create the file and write the stream header. Without a frame rate the header says
+    /// `F0:0`, "unknown"; players then fall back to 25 fps.
+    pub fn create(
+        path: &Path,
+        width: u32,
+        height: u32,
+        frame_rate: Option<(u32, u32)>,
+        full_range: bool,
+    ) -> anyhow::Result<Y4mWriter> {
+        let frame_size = yuv::nv12_len(width, height)
+            .with_context(|| format!("{width}x{height} is not a usable Y4M frame size"))?;
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        let (num, den) = frame_rate.unwrap_or((0, 0));
+        writeln!(
+            file,
+            "YUV4MPEG2 W{width} H{height} F{num}:{den} Ip A1:1 C420 XCOLORRANGE={}",
+            if full_range { "FULL" } else { "LIMITED" }
+        )
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Y4mWriter {
+            file,
+            width,
+            height,
+            frame_size,
+            frames: 0,
+        })
+    }
+
+    /// Append one NV12 frame of the writer's size.
+    pub fn write_frame(&mut self, nv12: &[u8]) -> anyhow::Result<()> {
+        let luma = self.frame_size / 3 * 2;
+        let chroma = luma / 4;
+        if nv12.len() < self.frame_size {
+            anyhow::bail!(
+                "Frame {} has {} bytes, expected {} for NV12 {}x{}",
+                self.frames + 1,
+                nv12.len(),
+                self.frame_size,
+                self.width,
+                self.height
+            );
+        }
+        let uv = &nv12[luma..luma + 2 * chroma];
+        let cb: Vec<u8> = uv.iter().step_by(2).copied().collect();
+        let cr: Vec<u8> = uv.iter().skip(1).step_by(2).copied().collect();
+
+        self.file.write_all(FRAME_HEADER)?;
+        self.file.write_all(&nv12[..luma])?;
+        self.file.write_all(&cb)?;
+        self.file.write_all(&cr)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flush the file; returns the number of frames written.
+    pub fn finish(mut self) -> anyhow::Result<usize> {
+        self.file.flush()?;
+        Ok(self.frames)
+    }
+}
+
+/// Whether `data` starts like a YUV4MPEG2 stream.
+pub fn is_y4m(data: &[u8]) -> bool {
+    data.starts_with(b"YUV4MPEG2 ")
+}
+
+impl Y4mReader {
+    /// Parse the stream header of a YUV4MPEG2 file and count its frames. Chroma siting
+    /// is not used.
+    pub fn open(mut file: File) -> anyhow::Result<Y4mReader> {
+        let mut start = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        (&mut file).take(256).read_to_end(&mut start)?;
+        let header = line(&start).context("Missing YUV4MPEG2 header")?;
+        let header_len = header.len() as u64 + 1;
+        let mut params = header.split(' ');
+        if params.next() != Some("YUV4MPEG2") {
+            anyhow::bail!("Not a YUV4MPEG2 file");
+        }
+
+        let (mut width, mut height, mut frame_rate, mut full_range) = (0, 0, None, None);
+        for param in params {
+            let Some((tag, value)) = param.split_at_checked(1) else {
+                continue;
+            };
+            match tag {
+                "W" => width = value.parse().context("Invalid width in Y4M header")?,
+                "H" => height = value.parse().context("Invalid height in Y4M header")?,
+                "F" => {
+                    frame_rate = value
+                        .split_once(':')
+                        .and_then(|(n, d)| Some((n.parse().ok()?, d.parse().ok()?)))
+                        .filter(|&(n, d)| n > 0 && d > 0)
+                }
+                // C420p10 and up are high bit depth; C420paldv is 8-bit PAL DV siting
+                "C" if !value.starts_with("420")
+                    || (value.starts_with("420p") && value != "420paldv") =>
+                {
+                    anyhow::bail!(
+                        "Unsupported Y4M colour space C{value}; only 8-bit 4:2:0 can be decoded"
+                    )
+                }
+                "X" => match value {
+                    "COLORRANGE=FULL" => full_range = Some(true),
+                    "COLORRANGE=LIMITED" => full_range = Some(false),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        if width == 0 || height == 0 || width % 2 != 0 || height % 2 != 0 {
+            anyhow::bail!(
+                "Unsupported Y4M frame size {width}x{height}; width and height must be even"
+            );
+        }
+
+        let frame_size = yuv::nv12_len(width, height)
+            .and_then(|size| u64::try_from(size).ok())
+            .with_context(|| format!("Y4M frame size {width}x{height} is too large"))?;
+        let stride = FRAME_HEADER.len() as u64 + frame_size;
+        let body = file.metadata()?.len().saturating_sub(header_len);
+        let frames = body / stride;
+        if frames == 0 && body == 0 {
+            anyhow::bail!("The Y4M file has no frames");
+        }
+
+        // Every frame is found by its offset, which only holds if each has a bare FRAME line
+        let mut first = [0; FRAME_HEADER.len()];
+        file.seek(SeekFrom::Start(header_len))?;
+        file.read_exact(&mut first)
+            .context("Frame 1 has no FRAME header")?;
+        if first != FRAME_HEADER {
+            if first.starts_with(b"FRAME") {
+                anyhow::bail!("Y4M frame parameters are not supported");
+            }
+            anyhow::bail!("Frame 1 has no FRAME header");
+        }
+        if body % stride != 0 {
+            anyhow::bail!(
+                "The file ends partway through frame {} ({} of {} bytes)",
+                frames + 1,
+                (body % stride).saturating_sub(FRAME_HEADER.len() as u64),
+                frame_size
+            );
+        }
+
+        Ok(Y4mReader {
+            file,
+            width,
+            height,
+            frame_rate,
+            full_range,
+            frames: frames as usize,
+            header_len,
+            frame_size: frame_size as usize,
+        })
+    }
+
+    /// Frame `index`, counting from 0, as NV12. Only that frame is read.
+    pub fn frame(&mut self, index: usize) -> anyhow::Result<Vec<u8>> {
+        if index >= self.frames {
+            anyhow::bail!("There is no frame {}", index + 1);
+        }
+        let stride = (FRAME_HEADER.len() + self.frame_size) as u64;
+        self.file
+            .seek(SeekFrom::Start(self.header_len + index as u64 * stride))?;
+        let mut data = vec![0; FRAME_HEADER.len() + self.frame_size];
+        self.file
+            .read_exact(&mut data)
+            .with_context(|| format!("Failed to read frame {}", index + 1))?;
+        let Some(body) = data.strip_prefix(FRAME_HEADER) else {
+            anyhow::bail!("Frame {} has no FRAME header", index + 1);
+        };
+
+        let luma = self.frame_size / 3 * 2;
+        let chroma = luma / 4;
+        let (cb, cr) = body[luma..].split_at(chroma);
+        let mut nv12 = Vec::with_capacity(self.frame_size);
+        nv12.extend_from_slice(&body[..luma]);
+        for (&u, &v) in cb.iter().zip(cr) {
+            nv12.extend_from_slice(&[u, v]);
+        }
+        Ok(nv12)
+    }
+}
+
+/// The text up to the first newline.
+fn line(data: &[u8]) -> Option<&str> {
+    let end = data.iter().take(256).position(|&b| b == b'\n')?;
+    std::str::from_utf8(&data[..end]).ok()
+}
@@ -16,6 +16,41 @@ pub const BT709: YuvMatrix = YuvMatrix {
     kb: 0.0722,
 };
 
+pub const BT2020: YuvMatrix = YuvMatrix {
+    name: "BT.2020",
+    kr: 0.2627,
+    kb: 0.0593,
+};
+
+/// Matrices a camera might plausibly encode with, in the order results are reported.
+pub const CANDIDATE_MATRICES: [&YuvMatrix; 3] = [&BT601, &BT709, &BT2020];
+
+/// How many decoded pixels fell outside 0–255 in at least one channel.
+#[derive(Default)]
+pub struct ClipStats {
+    pub pixels: u64,
+    pub clipped_low: u64,
+    pub clipped_high: u64,
+}
+
+impl ClipStats {
+    pub fn low_percent(&self) -> f64 {
+        percent(self.clipped_low, self.pixels)
+    }
+
+    pub fn high_percent(&self) -> f64 {
+        percent(self.clipped_high, self.pixels)
+    }
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
 /// Convert NV12 frame to RGB24.
 ///
 /// NV12 layout: Y plane (width * height bytes), then interleaved UV plane (width * height/2 bytes).
@@ -27,11 +62,62 @@ pub fn nv12_to_rgb24(
     matrix: &YuvMatrix,
     full_range: bool,
 ) -> Vec<u8> {
+    nv12_to_rgb24_with_stats(data, width, height, matrix, full_range).0
+}
+
+/// Like [`nv12_to_rgb24`], also counting pixels that had to be clipped.
+pub fn nv12_to_rgb24_with_stats(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    matrix: &YuvMatrix,
+    full_range: bool,
+) -> (Vec<u8>, ClipStats) {
     let w = width as usize;
     let h = height as usize;
     let y_plane = &data[..w * h];
     let uv_plane = &data[w * h..];
 
+    let mut rgb = vec![0u8; w * h * 3];
+    let mut stats = ClipStats {
+        pixels: (w * h) as u64,
+        ..Default::default()
+    };
+
+    for row in 0..h {
+        for col in 0..w {
+            let y_idx = row * w + col;
+            let uv_row = row / 2;
+            let uv_col = (col / 2) * 2; // each UV pair covers 2 pixels
+            let uv_idx = uv_row * w + uv_col;
+
+            let [r, g, b] = ycbcr_to_rgb(
+                y_plane[y_idx] as f64,
+                uv_plane[uv_idx] as f64,
+                uv_plane[uv_idx + 1] as f64,
+                matrix,
+                full_range,
+            );
+            if r < -0.5 || g < -0.5 || b < -0.5 {
+                stats.clipped_low += 1;
+            }
+            if r > 255.5 || g > 255.5 || b > 255.5 {
+                stats.clipped_high += 1;
+            }
+
+            let out_idx = y_idx * 3;
+            rgb[out_idx] = clamp_u8(r);
+            rgb[out_idx + 1] = clamp_u8(g);
+            rgb[out_idx + 2] = clamp_u8(b);
+        }
+    }
+
+    (rgb, stats)
+}
+
+/// Convert one Y/Cb/Cr sample (code values, may be fractional averages) to
+/// unclamped RGB on a 0–255 scale.
+pub fn ycbcr_to_rgb(y: f64, cb: f64, cr: f64, matrix: &YuvMatrix, full_range: bool) -> [f64; 3] {
     let kg = 1.0 - matrix.kr - matrix.kb;
 
     // Range parameters
@@ -41,33 +127,73 @@ pub fn nv12_to_rgb24(
         (16.0, 219.0, 224.0)
     };
 
-    let mut rgb = vec![0u8; w * h * 3];
+    let y = (y - y_offset) / y_scale;
+    let cb = (cb - 128.0) / uv_scale;
+    let cr = (cr - 128.0) / uv_scale;
 
-    for row in 0..h {
-        for col in 0..w {
-            let y_idx = row * w + col;
-            let uv_row = row / 2;
-            let uv_col = (col / 2) * 2; // each UV pair covers 2 pixels
-            let uv_idx = uv_row * w + uv_col;
+    let r = y + (2.0 * (1.0 - matrix.kr)) * cr;
+    let g = y
+        - (2.0 * (1.0 - matrix.kb) * matrix.kb / kg) * cb
+        - (2.0 * (1.0 - matrix.kr) * matrix.kr / kg) * cr;
+    let b = y + (2.0 * (1.0 - matrix.kb)) * cb;
 
-            let y = (y_plane[y_idx] as f64 - y_offset) / y_scale;
-            let cb = (uv_plane[uv_idx] as f64 - 128.0) / uv_scale;
-            let cr = (uv_plane[uv_idx + 1] as f64 - 128.0) / uv_scale;
+    [r * 255.0, g * 255.0, b * 255.0]
+}
 
-            let r = y + (2.0 * (1.0 - matrix.kr)) * cr;
-            let g = y
-                - (2.0 * (1.0 - matrix.kb) * matrix.kb / kg) * cb
-                - (2.0 * (1.0 - matrix.kr) * matrix.kr / kg) * cr;
-            let b = y + (2.0 * (1.0 - matrix.kb)) * cb;
+/// Bytes in an NV12 frame of `width` × `height`. `None` for zero or odd dimensions, which
+/// 4:2:0 chroma can't cover, and for sizes too large to address.
+pub fn nv12_len(width: u32, height: u32) -> Option<usize> {
+    if width == 0 || height == 0 || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+        return None;
+    }
+    let luma = (width as usize).checked_mul(height as usize)?;
+    luma.checked_mul(3).map(|n| n / 2)
+}
 
-            let out_idx = y_idx * 3;
-            rgb[out_idx] = clamp_u8(r * 255.0);
-            rgb[out_idx + 1] = clamp_u8(g * 255.0);
-            rgb[out_idx + 2] = clamp_u8(b * 255.0);
+/// Repack packed 4:2:2 YUYV (Y0 Cb Y1 Cr) with `stride` bytes per row into NV12,
+/// averaging chroma over each pair of rows. `None` if the buffer is too short.
+pub fn yuyv_to_nv12(data: &[u8], width: u32, height: u32, stride: u32) -> Option<Vec<u8>> {
+    let (w, h, stride) = (width as usize, height as usize, stride as usize);
+    if h == 0 || stride < w * 2 || data.len() < stride * (h - 1) + w * 2 {
+        return None;
+    }
+    let row = |y: usize| &data[y * stride..y * stride + w * 2];
+
+    let mut out = vec![0u8; w * h + w * (h / 2)];
+    let (y_plane, uv_plane) = out.split_at_mut(w * h);
+    for y in 0..h {
+        for (dst, px) in y_plane[y * w..(y + 1) * w]
+            .iter_mut()
+            .zip(row(y).chunks_exact(2))
+        {
+            *dst = px[0];
+        }
+    }
+    for y in 0..h / 2 {
+        let (top, bottom) = (row(y * 2), row(y * 2 + 1));
+        let dst = &mut uv_plane[y * w..(y + 1) * w];
+        for (i, uv) in dst.chunks_exact_mut(2).enumerate() {
+            let q = i * 4;
+            uv[0] = (top[q + 1] as u16 + bottom[q + 1] as u16).div_ceil(2) as u8;
+            uv[1] = (top[q + 3] as u16 + bottom[q + 3] as u16).div_ceil(2) as u8;
         }
     }
+    Some(out)
+}
 
-    rgb
+/// Drop row padding from an NV12 buffer whose planes use `stride` bytes per row.
+/// `None` if the buffer is too short.
+pub fn nv12_unpad(data: &[u8], width: u32, height: u32, stride: u32) -> Option<Vec<u8>> {
+    let (w, h, stride) = (width as usize, height as usize, stride as usize);
+    let rows = h + h / 2;
+    if h == 0 || stride < w || data.len() < stride * (rows - 1) + w {
+        return None;
+    }
+    let mut out = Vec::with_capacity(w * rows);
+    for y in 0..rows {
+        out.extend_from_slice(&data[y * stride..y * stride + w]);
+    }
+    Some(out)
 }
 
 fn clamp_u8(v: f64) -> u8 {